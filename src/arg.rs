@@ -1,24 +1,65 @@
 extern crate clap;
 
+use chrono::Duration;
 use clap::{App, Arg};
+use regex::Regex;
 use std::fmt;
 use super::VERSION;
 use super::docker_registry_type;
+use super::image_registry;
+use super::version;
+use super::image;
+use super::space;
+use super::plan;
+use super::pattern;
+use super::git;
+use super::promote;
 
 pub struct Args {
     pub is_dry_run: bool,
     pub keep_count: usize,
     pub keep_count_snapshot: usize,
+    pub keep_count_describe: usize,
+    pub keep_per_line: Option<usize>,
+    pub keep_lines: Option<usize>,
+    // (daily, weekly, monthly). None if none of them are specified.
+    pub snapshot_buckets: Option<(usize, usize, usize)>,
     pub repository_type: docker_registry_type::DockerRegistryType,
+    pub docker_host: Option<String>,
+    pub docker_context: Option<String>,
     pub show_version: bool,
+    pub commit_tag_pattern: Option<Regex>,
+    pub snapshot_markers: Vec<version::SnapshotMarker>,
+    pub variants: Vec<String>,
+    pub is_component_enabled: bool,
+    pub remove_by_id: bool,
+    pub target: Option<space::Target>,
+    pub keep_floor: usize,
+    pub protected_tags: Vec<String>,
+    pub remove_dangling: Option<Duration>,
+    pub pins: Vec<pattern::ImagePattern>,
+    pub delete_ranges: Vec<pattern::ImagePattern>,
+    pub git_repo: Option<String>,
+    pub git_merged_into: Option<String>,
+    pub branch_maps: Vec<git::BranchMap>,
+    pub branch_ttl: Option<Duration>,
+    pub max_branches: Option<usize>,
+    pub feature_branch: Option<image::BranchPattern>,
+    pub release_groups: Vec<Vec<String>>,
+    pub promote: Option<promote::PromoteTags>,
 }
 
 impl fmt::Display for Args {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "Args(is_dry_run: {}, keep_count: {}, keep_count_snapshot: {}, show_version: {})",
-            self.is_dry_run, self.keep_count, self.keep_count_snapshot, self.show_version
+            "Args(is_dry_run: {}, keep_count: {}, keep_count_snapshot: {}, keep_count_describe: {}, keep_per_line: {:?}, keep_lines: {:?}, snapshot_buckets: {:?}, docker_host: {:?}, docker_context: {:?}, show_version: {}, commit_tag_pattern: {:?}, snapshot_markers: {:?}, variants: {:?}, is_component_enabled: {}, remove_by_id: {}, target: {:?}, keep_floor: {}, protected_tags: {:?}, remove_dangling: {:?}, pins: {:?}, delete_ranges: {:?}, git_repo: {:?}, git_merged_into: {:?}, branch_maps: {:?}, branch_ttl: {:?}, max_branches: {:?}, feature_branch: {:?}, release_groups: {:?}, promote: {:?})",
+            self.is_dry_run, self.keep_count, self.keep_count_snapshot, self.keep_count_describe, self.keep_per_line, self.keep_lines, self.snapshot_buckets, self.docker_host, self.docker_context, self.show_version,
+            self.commit_tag_pattern.as_ref().map(|re| re.as_str()), self.snapshot_markers, self.variants, self.is_component_enabled, self.remove_by_id,
+            self.target, self.keep_floor, self.protected_tags,
+            self.remove_dangling, self.pins, self.delete_ranges,
+            self.git_repo, self.git_merged_into, self.branch_maps, self.branch_ttl, self.max_branches, self.feature_branch,
+            self.release_groups, self.promote
         )
     }
 }
@@ -30,7 +71,95 @@ fn parse_int(s: &str, var_name: &str) -> usize {
             panic!("{} should be integer. {:?}", var_name, err),
     }
 }
-    
+
+// Parse duration such as '7d'. Units are s, m, h, d and w.
+fn parse_duration(s: &str, var_name: &str) -> Duration {
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => (&s[..idx], &s[idx..]),
+        None => (s, "s")
+    };
+    let n = parse_int(num, var_name) as i64;
+
+    match unit {
+        "s" => Duration::seconds(n),
+        "m" => Duration::minutes(n),
+        "h" => Duration::hours(n),
+        "d" => Duration::days(n),
+        "w" => Duration::weeks(n),
+        other => panic!("{} has invalid unit '{}'. Specify s, m, h, d or w.", var_name, other),
+    }
+}
+
+fn parse_commit_tag_pattern(is_enabled: bool, pattern: Option<&str>) -> Option<Regex> {
+    let pattern = match pattern {
+        Some(p) => p,
+        None if is_enabled => image_registry::DEFAULT_COMMIT_TAG_PATTERN,
+        None => return None,
+    };
+
+    match Regex::new(pattern) {
+        Result::Ok(re) => Some(re),
+        Result::Err(err) =>
+            panic!("commit tag pattern is invalid. {:?}", err),
+    }
+}
+
+// The default marker is always used unless it is overridden by the same marker with keep count.
+fn parse_snapshot_markers<'a>(specs: impl Iterator<Item = &'a str>) -> Vec<version::SnapshotMarker> {
+    let mut markers = vec![version::SnapshotMarker { text: version::DEFAULT_SNAPSHOT_MARKER.to_string(), keep_count: None }];
+
+    for spec in specs {
+        let marker = match version::parse_marker(spec) {
+            Result::Ok(marker) => marker,
+            Result::Err(err) => panic!("{}", err),
+        };
+        markers.retain(|m| m.text != marker.text);
+        markers.push(marker);
+    }
+
+    markers
+}
+
+// The default tags are used unless specified. An empty tag disables the protection.
+fn parse_protected_tags<'a>(tags: Option<impl Iterator<Item = &'a str>>) -> Vec<String> {
+    match tags {
+        Some(tags) => tags.filter(|t| !t.is_empty()).map(|t| t.to_string()).collect(),
+        None => plan::DEFAULT_PROTECTED_TAGS.iter().map(|t| t.to_string()).collect(),
+    }
+}
+
+fn read_keep_file(path: &str) -> Vec<pattern::ImagePattern> {
+    let content = match std::fs::read_to_string(path) {
+        Result::Ok(content) => content,
+        Result::Err(err) => panic!("Cannot read keep file '{}'. {:?}", path, err),
+    };
+    pattern::parse_keep_file(&content).unwrap()
+}
+
+// '--delete' patterns and '--older-than-version' that is the same as '--delete <version'.
+fn parse_delete_ranges<'a>(patterns: impl Iterator<Item = &'a str>, older_than: Option<&str>) -> Vec<pattern::ImagePattern> {
+    patterns.map(|p| pattern::ImagePattern::parse(p).unwrap())
+        .chain(older_than.map(|v| pattern::ImagePattern::parse(&format!("<{}", v)).unwrap()))
+        .collect()
+}
+
+// Unspecified bucket counts are 0.
+fn parse_snapshot_buckets(daily: Option<&str>, weekly: Option<&str>, monthly: Option<&str>) -> Option<(usize, usize, usize)> {
+    if daily.is_none() && weekly.is_none() && monthly.is_none() {
+        return None;
+    }
+    let count = |c: Option<&str>, var_name: &str| c.map(|c| parse_int(c, var_name)).unwrap_or(0);
+    Some((count(daily, "daily keep count"), count(weekly, "weekly keep count"), count(monthly, "monthly keep count")))
+}
+
+fn parse_target(free: Option<&str>, usage: Option<&str>) -> Option<space::Target> {
+    match (free, usage) {
+        (Some(f), _) => Some(space::Target::Free(space::parse_size(f).unwrap())),
+        (None, Some(u)) => Some(space::Target::Usage(space::parse_percent(u).unwrap())),
+        (None, None) => None,
+    }
+}
+
 pub fn parse_arg() -> Args {
     let app = App::new("trimimages")
         .version(VERSION)
@@ -50,11 +179,186 @@ pub fn parse_arg() -> Args {
              .long("keep-snapshot")
              .default_value("1")
         )
+        .arg(Arg::with_name("keep-describe")
+             .help("Keep count for git describe versioned image such as 1.2.3-14-gabc1234.")
+             .long("keep-describe")
+             .default_value("1")
+        )
+        .arg(Arg::with_name("keep-per-line")
+             .help("Also keep this count of the newest canonical versions of each release line (major.minor).")
+             .long("keep-per-line")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("keep-lines")
+             .help("Also keep the newest canonical version of each of this count of the newest release lines (major.minor).")
+             .long("keep-lines")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("keep-daily")
+             .help("Also keep the newest snapshot version of each of this count of the last days.")
+             .long("keep-daily")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("keep-weekly")
+             .help("Also keep the newest snapshot version of each of this count of the last weeks.")
+             .long("keep-weekly")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("keep-monthly")
+             .help("Also keep the newest snapshot version of each of this count of the last months.")
+             .long("keep-monthly")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("snapshot-marker")
+             .help("Additional snapshot marker such as -dev (suffix) or dev- (prefix). Append :count to specify its own keep count.")
+             .long("snapshot-marker")
+             .multiple(true)
+             .number_of_values(1)
+             .allow_hyphen_values(true)
+        )
+        .arg(Arg::with_name("variant")
+             .help("Tag suffix such as alpine treated as a variant of the version instead of a branch. Can be specified multiple times.")
+             .long("variant")
+             .multiple(true)
+             .number_of_values(1)
+        )
+        .arg(Arg::with_name("component-tags")
+             .help("Recognize component prefixed tags such as api-1.2.3. Each component keeps its own versions.")
+             .long("component-tags")
+        )
+        .arg(Arg::with_name("remove-by-id")
+             .help("Remove the image by id when all of its tags are to be deleted.")
+             .long("remove-by-id")
+        )
+        .arg(Arg::with_name("target-free")
+             .help("Delete images oldest first until the free space reaches this size such as 50G. Keep counts are relaxed down to --keep-floor if needed.")
+             .long("target-free")
+             .takes_value(true)
+             .conflicts_with("target-usage")
+        )
+        .arg(Arg::with_name("target-usage")
+             .help("Delete images oldest first until the disk usage goes down to this percent such as 70%. Keep counts are relaxed down to --keep-floor if needed.")
+             .long("target-usage")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("keep-floor")
+             .help("Minimum keep count when keep counts are relaxed by --target-free or --target-usage.")
+             .long("keep-floor")
+             .default_value("1")
+        )
+        .arg(Arg::with_name("protect-tag")
+             .help("Keep images having this tag such as latest. Can be specified multiple times. Default to latest, stable and prod. Specify an empty string to protect none.")
+             .long("protect-tag")
+             .multiple(true)
+             .number_of_values(1)
+        )
+        .arg(Arg::with_name("remove-dangling")
+             .help("Also remove dangling (untagged) images older than this age such as 7d. Units are s, m, h, d and w.")
+             .long("remove-dangling")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("keep-file")
+             .help("File listing images never to be deleted. One repo:tag or repo:version-range such as myapp:>=2.3 <2.4 per line.")
+             .long("keep-file")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("delete")
+             .help("Delete tags matching repo:version-range such as myapp:<2.0 instead of applying keep counts. Can be specified multiple times.")
+             .long("delete")
+             .multiple(true)
+             .number_of_values(1)
+             .allow_hyphen_values(true)
+        )
+        .arg(Arg::with_name("older-than-version")
+             .help("Delete tags older than this version in all repositories instead of applying keep counts. Same as --delete '<version'.")
+             .long("older-than-version")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("branch-ttl")
+             .help("Delete every image of a branch whose newest image is older than this age such as 30d. Units are s, m, h, d and w.")
+             .long("branch-ttl")
+             .takes_value(true)
+             .requires("feature-branch")
+        )
+        .arg(Arg::with_name("max-branches")
+             .help("Keep only this count of the most recently active branches in each repository.")
+             .long("max-branches")
+             .takes_value(true)
+             .requires("feature-branch")
+        )
+        .arg(Arg::with_name("feature-branch")
+             .help("Regex of feature branch names such as BR\\d+. Only matching branches are deleted by --branch-ttl and --max-branches.")
+             .long("feature-branch")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("release-group")
+             .help("Comma separated repositories sharing a release cadence such as shop/api,shop/worker. Versions are kept as a unit across them. Can be specified multiple times.")
+             .long("release-group")
+             .multiple(true)
+             .number_of_values(1)
+        )
+        .arg(Arg::with_name("git-repo")
+             .help("Path to the git repository. Images of branches deleted from it are deleted. Requires --branch-map.")
+             .long("git-repo")
+             .takes_value(true)
+             .requires("branch-map")
+        )
+        .arg(Arg::with_name("git-merged-into")
+             .help("Also delete images of branches merged into this git ref such as origin/main. Requires --git-repo.")
+             .long("git-merged-into")
+             .takes_value(true)
+             .requires("git-repo")
+        )
+        .arg(Arg::with_name("branch-map")
+             .help("Map image branch names to git branch names by regex=replacement such as BR(\\d+)=feature/BR-$1. Can be specified multiple times. Only mapped branches are checked.")
+             .long("branch-map")
+             .multiple(true)
+             .number_of_values(1)
+        )
+        .arg(Arg::with_name("promote")
+             .help("After pruning, point floating tags at the newest kept version of each group.")
+             .long("promote")
+        )
+        .arg(Arg::with_name("promote-tag")
+             .help("Floating tag for canonical versions promoted by --promote. Empty to disable.")
+             .long("promote-tag")
+             .default_value("latest")
+        )
+        .arg(Arg::with_name("promote-snapshot-tag")
+             .help("Floating tag for snapshot versions promoted by --promote. Empty to disable.")
+             .long("promote-snapshot-tag")
+             .default_value("latest-snapshot")
+        )
+        .arg(Arg::with_name("promote-branch-tag")
+             .help("Floating tag for versions of each branch promoted by --promote. {branch} is replaced by the branch. Empty to disable.")
+             .long("promote-branch-tag")
+             .default_value("{branch}-latest")
+        )
         .arg(Arg::with_name("repository-type")
              .help("Specify docker image repository type (docker or ibmcr).")
              .long("repository-type")
              .default_value("docker")
         )
+        .arg(Arg::with_name("host")
+             .help("Docker daemon to connect such as ssh://user@builder or tcp://builder:2376. Only for docker repository type.")
+             .long("host")
+             .takes_value(true)
+             .conflicts_with("context")
+        )
+        .arg(Arg::with_name("context")
+             .help("Docker context to use. Only for docker repository type.")
+             .long("context")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("commit-tags")
+             .help("Also remove commit SHA tagged images. They are ordered by creation time and kept by --keep count.")
+             .long("commit-tags")
+        )
+        .arg(Arg::with_name("commit-tag-pattern")
+             .help("Regex for commit tags. Implies --commit-tags.")
+             .long("commit-tag-pattern")
+             .takes_value(true)
+        )
         .arg(Arg::with_name("show-version")
              .help("Show version.")
              .long("version")
@@ -62,12 +366,111 @@ pub fn parse_arg() -> Args {
         ;
 
     let matches = app.get_matches();
+    let repository_type = docker_registry_type::parse(matches.value_of("repository-type").unwrap()).unwrap();
+    if repository_type != docker_registry_type::DockerRegistryType::Docker && (matches.is_present("host") || matches.is_present("context")) {
+        panic!("--host and --context are only available for docker repository type.");
+    }
+    if (matches.is_present("host") || matches.is_present("context")) && (matches.is_present("target-free") || matches.is_present("target-usage")) {
+        panic!("--target-free and --target-usage are not available with --host or --context since disk usage of a remote docker engine cannot be obtained.");
+    }
 
     Args {
         is_dry_run: matches.is_present("dryrun"),
         keep_count: parse_int(matches.value_of("keep").unwrap(), "keep count"),
         keep_count_snapshot: parse_int(matches.value_of("keep-snapshot").unwrap(), "keep count snapshot"),
-        repository_type: docker_registry_type::parse(matches.value_of("repository-type").unwrap()).unwrap(),
+        keep_count_describe: parse_int(matches.value_of("keep-describe").unwrap(), "keep count describe"),
+        keep_per_line: matches.value_of("keep-per-line").map(|c| parse_int(c, "keep count per line")),
+        keep_lines: matches.value_of("keep-lines").map(|c| parse_int(c, "keep line count")),
+        snapshot_buckets: parse_snapshot_buckets(
+            matches.value_of("keep-daily"), matches.value_of("keep-weekly"), matches.value_of("keep-monthly")
+        ),
+        repository_type,
+        docker_host: matches.value_of("host").map(|h| h.to_string()),
+        docker_context: matches.value_of("context").map(|c| c.to_string()),
         show_version: matches.is_present("show-version"),
+        commit_tag_pattern: parse_commit_tag_pattern(
+            matches.is_present("commit-tags"), matches.value_of("commit-tag-pattern")
+        ),
+        snapshot_markers: parse_snapshot_markers(matches.values_of("snapshot-marker").into_iter().flatten()),
+        variants: matches.values_of("variant").into_iter().flatten().map(|v| v.to_string()).collect(),
+        is_component_enabled: matches.is_present("component-tags"),
+        remove_by_id: matches.is_present("remove-by-id"),
+        target: parse_target(matches.value_of("target-free"), matches.value_of("target-usage")),
+        keep_floor: parse_int(matches.value_of("keep-floor").unwrap(), "keep floor"),
+        protected_tags: parse_protected_tags(matches.values_of("protect-tag")),
+        remove_dangling: matches.value_of("remove-dangling").map(|age| parse_duration(age, "dangling image age")),
+        pins: matches.value_of("keep-file").map(read_keep_file).unwrap_or_default(),
+        branch_ttl: matches.value_of("branch-ttl").map(|ttl| parse_duration(ttl, "branch ttl")),
+        max_branches: matches.value_of("max-branches").map(|m| parse_int(m, "max branches")),
+        feature_branch: matches.value_of("feature-branch").map(|p| match image::BranchPattern::parse(p) {
+            Result::Ok(pattern) => pattern,
+            Result::Err(err) => panic!("{}", err),
+        }),
+        promote: if matches.is_present("promote") {
+            let tag = |name: &str| Some(matches.value_of(name).unwrap().to_string()).filter(|t| !t.is_empty());
+            Some(promote::PromoteTags {
+                canonical: tag("promote-tag"), snapshot: tag("promote-snapshot-tag"), branch: tag("promote-branch-tag")
+            })
+        } else {
+            None
+        },
+        release_groups: matches.values_of("release-group").into_iter().flatten()
+            .map(|g| g.split(',').map(|r| r.trim().to_string()).filter(|r| !r.is_empty()).collect())
+            .collect(),
+        git_repo: matches.value_of("git-repo").map(|p| p.to_string()),
+        git_merged_into: matches.value_of("git-merged-into").map(|r| r.to_string()),
+        branch_maps: matches.values_of("branch-map").into_iter().flatten()
+            .map(|m| git::parse_branch_map(m).unwrap())
+            .collect(),
+        delete_ranges: parse_delete_ranges(
+            matches.values_of("delete").into_iter().flatten(), matches.value_of("older-than-version")
+        ),
     }
 }
+
+#[test]
+fn parse_snapshot_markers_test() {
+    let markers = parse_snapshot_markers(vec!["-dev", "-SNAPSHOT:2"].into_iter());
+    assert_eq!(markers, vec![
+        version::SnapshotMarker { text: "-dev".to_string(), keep_count: None },
+        version::SnapshotMarker { text: "-SNAPSHOT".to_string(), keep_count: Some(2) },
+    ]);
+
+    let markers = parse_snapshot_markers(vec![].into_iter());
+    assert_eq!(markers, vec![version::SnapshotMarker { text: "-SNAPSHOT".to_string(), keep_count: None }]);
+}
+
+#[test]
+fn parse_protected_tags_test() {
+    assert_eq!(parse_protected_tags(None::<std::vec::IntoIter<&str>>), vec!["latest", "stable", "prod"]);
+    assert_eq!(parse_protected_tags(Some(vec!["release"].into_iter())), vec!["release"]);
+    assert!(parse_protected_tags(Some(vec![""].into_iter())).is_empty());
+}
+
+#[test]
+fn parse_duration_test() {
+    assert_eq!(parse_duration("7d", "age"), Duration::days(7));
+    assert_eq!(parse_duration("12h", "age"), Duration::hours(12));
+    assert_eq!(parse_duration("0", "age"), Duration::zero());
+}
+
+#[test]
+#[should_panic]
+fn parse_duration_invalid_unit_test() {
+    parse_duration("7y", "age");
+}
+
+#[test]
+fn parse_delete_ranges_test() {
+    let ranges = parse_delete_ranges(vec!["myapp:<2.0"].into_iter(), Some("1.5"));
+    assert_eq!(ranges.len(), 2);
+    assert_eq!(ranges[0].to_string(), "myapp:<2.0");
+    assert_eq!(ranges[1].to_string(), "<1.5");
+    assert!(parse_delete_ranges(Vec::new().into_iter(), None).is_empty());
+}
+
+#[test]
+fn parse_snapshot_buckets_test() {
+    assert_eq!(parse_snapshot_buckets(None, None, None), None);
+    assert_eq!(parse_snapshot_buckets(Some("7"), None, Some("12")), Some((7, 0, 12)));
+}
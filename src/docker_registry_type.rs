@@ -13,6 +13,7 @@ pub fn parse(s: &str) -> Result<DockerRegistryType, String> {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn parse_test() {
     assert_eq!(parse("Docker"), Result::Ok(DockerRegistryType::Docker));
     assert_eq!(parse("IBMCR"), Result::Ok(DockerRegistryType::IbmCloudRegistry));
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::process::Command;
+
+use regex::Regex;
+
+// Maps image branch names to git branch names such as 'BR(\d+)=feature/BR-$1'.
+#[derive(Debug, Clone)]
+pub struct BranchMap {
+    re: Regex,
+    replacement: String,
+}
+
+impl PartialEq for BranchMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.re.as_str() == other.re.as_str() && self.replacement == other.replacement
+    }
+}
+
+// Parse 'regex=replacement'. The regex is anchored to match the whole branch name.
+pub fn parse_branch_map(s: &str) -> Result<BranchMap, String> {
+    let (re, replacement) = match s.rsplit_once('=') {
+        Some(pair) => pair,
+        None => return Result::Err(format!("Invalid branch map '{}'. Specify regex=replacement.", s)),
+    };
+
+    match Regex::new(&format!("^(?:{})$", re)) {
+        Result::Ok(re) => Result::Ok(BranchMap { re, replacement: replacement.to_string() }),
+        Result::Err(err) => Result::Err(format!("Invalid regex in branch map '{}'. {:?}", s, err)),
+    }
+}
+
+// Git branch name of the image branch by the first matching map. None if no map matches since
+// the suffix such as 'sdk' in '8-sdk' may not be a branch at all.
+pub fn git_branch(maps: &[BranchMap], branch: &str) -> Option<String> {
+    maps.iter()
+        .find(|m| m.re.is_match(branch))
+        .map(|m| m.re.replace(branch, m.replacement.as_str()).to_string())
+}
+
+// 'refs/heads/foo' and 'refs/remotes/origin/foo' are both 'foo'.
+fn branch_name(refname: &str) -> Option<&str> {
+    let name = match refname.strip_prefix("refs/heads/") {
+        Some(name) => name,
+        None => refname.strip_prefix("refs/remotes/")?.split_once('/')?.1,
+    };
+    if name == "HEAD" { None } else { Some(name) }
+}
+
+// Output of 'git for-each-ref --format=%(refname)'.
+fn parse_refs(out: &str) -> HashSet<String> {
+    out.lines().filter_map(branch_name).map(|name| name.to_string()).collect()
+}
+
+fn for_each_ref(repo: &str, extra_args: &[&str]) -> HashSet<String> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .arg("for-each-ref")
+        .arg("--format=%(refname)")
+        .args(extra_args)
+        .arg("refs/heads")
+        .arg("refs/remotes")
+        .output()
+        .expect("Cannot run 'git for-each-ref'. Please check git installation.");
+
+    if out.status.success() {
+        parse_refs(&String::from_utf8_lossy(&out.stdout))
+    } else {
+        println!("stdout: {}", String::from_utf8_lossy(&out.stdout));
+        println!("stderr: {}", String::from_utf8_lossy(&out.stderr));
+        panic!("git for-each-ref fails with status {}.", out.status);
+    }
+}
+
+pub struct Branches {
+    // Local and remote branch names without the remote name.
+    pub existing: HashSet<String>,
+    // Branches strictly behind the merged_into ref. Empty if merged_into is not specified.
+    pub merged: HashSet<String>,
+}
+
+// Branches that point to the same commit as merged_into are not regarded as merged since
+// they may have just been created.
+pub fn list_branches(repo: &str, merged_into: Option<&str>) -> Branches {
+    let existing = for_each_ref(repo, &[]);
+    let merged = match merged_into {
+        Some(base) => for_each_ref(repo, &["--merged", base, "--no-contains", base]),
+        None => HashSet::new(),
+    };
+    Branches { existing, merged }
+}
+
+// Returns image branches whose git branch is deleted or merged. value: reason
+pub fn stale_branches<'a>(
+    image_branches: impl Iterator<Item = &'a str>, branches: &Branches, maps: &[BranchMap]
+) -> HashMap<String, String> {
+    let mut stale = HashMap::new();
+    for branch in image_branches {
+        let git_branch = match git_branch(maps, branch) {
+            Some(b) => b,
+            None => continue,
+        };
+        if branches.merged.contains(&git_branch) {
+            stale.insert(branch.to_string(), format!("git branch {} is merged", git_branch));
+        } else if !branches.existing.contains(&git_branch) {
+            stale.insert(branch.to_string(), format!("git branch {} is deleted", git_branch));
+        }
+    }
+    stale
+}
+
+#[test]
+fn git_branch_test() {
+    assert_eq!(git_branch(&[], "BR102"), None);
+
+    let maps = vec![parse_branch_map(r"BR(\d+)=feature/BR-$1").unwrap(), parse_branch_map("dev=develop").unwrap()];
+    assert_eq!(git_branch(&maps, "BR102"), Some("feature/BR-102".to_string()));
+    assert_eq!(git_branch(&maps, "dev"), Some("develop".to_string()));
+    assert_eq!(git_branch(&maps, "sdk"), None);
+    assert_eq!(git_branch(&maps, "xBR102"), None);
+
+    assert!(parse_branch_map("BR102").is_err());
+    assert!(parse_branch_map("BR(=x").is_err());
+}
+
+#[test]
+fn parse_refs_test() {
+    let refs = parse_refs("refs/heads/main\nrefs/heads/feature/BR-102\nrefs/remotes/origin/HEAD\nrefs/remotes/origin/feature/BR-103\n");
+    let mut names: Vec<&String> = refs.iter().collect();
+    names.sort();
+    assert_eq!(names, vec!["feature/BR-102", "feature/BR-103", "main"]);
+}
+
+#[test]
+fn stale_branches_test() {
+    let branches = Branches {
+        existing: vec!["main".to_string(), "feature/BR-102".to_string(), "feature/BR-103".to_string()].into_iter().collect(),
+        merged: vec!["feature/BR-103".to_string()].into_iter().collect(),
+    };
+    let maps = vec![parse_branch_map(r"BR(\d+)=feature/BR-$1").unwrap()];
+
+    let stale = stale_branches(vec!["BR102", "BR103", "BR104", "sdk"].into_iter(), &branches, &maps);
+    assert_eq!(stale.len(), 2);
+    assert_eq!(stale["BR103"], "git branch feature/BR-103 is merged");
+    assert_eq!(stale["BR104"], "git branch feature/BR-104 is deleted");
+}
@@ -4,14 +4,32 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt;
 
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use regex::Regex;
+
 use super::version;
+use super::label;
 
 #[cfg(test)]
 use super::image;
 
 pub struct ImageEntry {
     pub id: String,
+    // The tag without a variant if any.
     pub ver: version::Version,
+    // Other tags of the same version such as 'v1.2' for '1.2'. Kept or deleted together with ver.
+    pub aliases: Vec<Alias>,
+}
+
+pub struct Alias {
+    pub id: String,
+    pub tag: String,
+}
+
+impl fmt::Debug for Alias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Alias [id: {}, tag: {}]", self.id, self.tag)
+    }
 }
 
 impl PartialEq for ImageEntry {
@@ -30,80 +48,456 @@ impl Hash for ImageEntry {
 
 impl fmt::Debug for ImageEntry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "ImageEntry [id: {}, ver: {}]", self.id, self.ver)
+        write!(f, "ImageEntry [id: {}, ver: {}, aliases: {:?}]", self.id, self.ver, self.aliases)
+    }
+}
+
+// Image whose tag has no version order (e.g. a git commit SHA). Ranked by creation time instead.
+pub struct CommitEntry {
+    pub id: String,
+    pub tag: String,
+    pub created: DateTime<Utc>,
+}
+
+impl fmt::Debug for CommitEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CommitEntry [id: {}, tag: {}, created: {}]", self.id, self.tag, self.created)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ImageRef {
+    pub repo: String,
+    pub tag: String,
+}
+
+impl fmt::Display for ImageRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.repo, self.tag)
+    }
+}
+
+#[derive(PartialEq, Clone, Default)]
+pub struct KeepPolicy {
+    pub canonical: usize,
+    pub snapshot: usize,
+    // Keep count for git describe versions such as '1.2.3-14-gabc1234'.
+    pub describe: usize,
+    // key: snapshot marker. Markers not listed here are kept by snapshot count.
+    pub markers: HashMap<String, usize>,
+    // key: repository. Overrides every keep count of the repository.
+    pub repositories: HashMap<String, usize>,
+    // Canonical versions also kept by release line (major.minor).
+    pub lines: LinePolicy,
+    // Snapshot versions also kept by creation time. None if disabled.
+    pub buckets: Option<BucketPolicy>,
+    // Every version of these branches is deleted.
+    pub stale_branches: HashSet<String>,
+    // Repositories sharing a release cadence such as shop/api and shop/worker.
+    pub release_groups: Vec<Vec<String>>,
+    // Every version of a branch whose newest image is created before this is deleted.
+    pub branch_active_since: Option<DateTime<Utc>>,
+    // Only this count of the most recently active branches are kept in each repository.
+    pub max_branches: Option<usize>,
+    // Only branches matching this are deleted by branch_active_since and max_branches, so that
+    // suffixes such as 'sdk' in '8-sdk' are not deleted as inactive branches.
+    pub feature_branch: Option<BranchPattern>,
+}
+
+// Regex matching the whole branch name such as 'BR\d+'.
+#[derive(Clone, Debug)]
+pub struct BranchPattern(Regex);
+
+impl BranchPattern {
+    pub fn parse(s: &str) -> Result<BranchPattern, String> {
+        match Regex::new(&format!("^(?:{})$", s)) {
+            Result::Ok(re) => Result::Ok(BranchPattern(re)),
+            Result::Err(err) => Result::Err(format!("Invalid feature branch pattern '{}'. {:?}", s, err)),
+        }
+    }
+
+    pub fn matches(&self, branch: &str) -> bool {
+        self.0.is_match(branch)
+    }
+}
+
+impl PartialEq for BranchPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+// Keep the newest snapshot of each of the last 'daily' days, 'weekly' weeks and 'monthly' months
+// including today. Weeks start on Monday.
+#[derive(PartialEq, Clone, Debug)]
+pub struct BucketPolicy {
+    pub daily: usize,
+    pub weekly: usize,
+    pub monthly: usize,
+    pub today: NaiveDate,
+}
+
+impl BucketPolicy {
+    // Buckets the date belongs to. ('d', days ago), ('w', weeks ago) or ('m', months ago).
+    fn buckets(&self, date: NaiveDate) -> Vec<(char, i64)> {
+        let monday = |d: NaiveDate| d - Duration::days(d.weekday().num_days_from_monday() as i64);
+        let days = (self.today - date).num_days().max(0);
+        let weeks = ((monday(self.today) - monday(date)).num_days() / 7).max(0);
+        let months = ((self.today.year() * 12 + self.today.month0() as i32) - (date.year() * 12 + date.month0() as i32)).max(0) as i64;
+
+        [('d', days, self.daily), ('w', weeks, self.weekly), ('m', months, self.monthly)].iter()
+            .filter(|(_, ago, count)| *ago < *count as i64)
+            .map(|(unit, ago, _)| (*unit, *ago))
+            .collect()
+    }
+
+    // Returns indices of the kept versions. Versions without creation time are not kept.
+    fn kept(&self, versions: &[&ImageEntry], created: &HashMap<String, DateTime<Utc>>) -> HashSet<usize> {
+        let mut newest_first: Vec<(usize, &DateTime<Utc>)> = versions.iter().enumerate()
+            .filter_map(|(i, e)| created.get(&e.id).map(|c| (i, c)))
+            .collect();
+        newest_first.sort_by(|a, b| b.1.cmp(a.1));
+
+        let mut seen: HashSet<(char, i64)> = HashSet::new();
+        let mut kept = HashSet::new();
+        for (i, c) in newest_first {
+            for bucket in self.buckets(c.date_naive()) {
+                if seen.insert(bucket) {
+                    kept.insert(i);
+                }
+            }
+        }
+        kept
     }
 }
 
+// Keep the newest per_line versions of each of the newest 'lines' release lines.
+// per_line defaults to 1 and lines defaults to all lines. Disabled if both are None.
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct LinePolicy {
+    pub per_line: Option<usize>,
+    pub lines: Option<usize>,
+}
+
+impl LinePolicy {
+    // Returns indices of the kept versions. The versions should be sorted newest first.
+    fn kept(&self, sorted: &[&ImageEntry]) -> HashSet<usize> {
+        let mut kept = HashSet::new();
+        if self.per_line.is_none() && self.lines.is_none() {
+            return kept;
+        }
+
+        let mut line_index = 0;
+        let mut count_in_line = 0;
+        for (i, e) in sorted.iter().enumerate() {
+            if 0 < i {
+                if sorted[i - 1].ver.release_line() == e.ver.release_line() {
+                    count_in_line += 1;
+                } else {
+                    line_index += 1;
+                    count_in_line = 0;
+                }
+            }
+
+            if self.lines.map_or(true, |lines| line_index < lines) && count_in_line < self.per_line.unwrap_or(1) {
+                kept.insert(i);
+            }
+        }
+        kept
+    }
+}
+
+impl KeepPolicy {
+    pub fn keep_count(&self, repo: &str, ver: &version::Version) -> usize {
+        if let Some(count) = self.repositories.get(repo) {
+            return *count;
+        }
+        if ver.describe.is_some() {
+            return self.describe;
+        }
+        match &ver.marker {
+            None => self.canonical,
+            Some(m) => *self.markers.get(m).unwrap_or(&self.snapshot),
+        }
+    }
+
+    // Reduce every keep count by step, but not below floor.
+    pub fn relaxed(&self, step: usize, floor: usize) -> KeepPolicy {
+        let relax = |count: usize| count.saturating_sub(step).max(count.min(floor));
+        KeepPolicy {
+            canonical: relax(self.canonical),
+            snapshot: relax(self.snapshot),
+            describe: relax(self.describe),
+            markers: self.markers.iter().map(|(m, count)| (m.clone(), relax(*count))).collect(),
+            repositories: self.repositories.iter().map(|(r, count)| (r.clone(), relax(*count))).collect(),
+            lines: LinePolicy { per_line: self.lines.per_line.map(relax), lines: self.lines.lines.map(relax) },
+            buckets: self.buckets.as_ref().map(|b| BucketPolicy {
+                daily: relax(b.daily), weekly: relax(b.weekly), monthly: relax(b.monthly), today: b.today
+            }),
+            stale_branches: self.stale_branches.clone(),
+            release_groups: self.release_groups.clone(),
+            branch_active_since: self.branch_active_since,
+            max_branches: self.max_branches.map(relax),
+            feature_branch: self.feature_branch.clone(),
+        }
+    }
+}
+
+// Versions are kept in each group of (component, snapshot marker, branch, is git describe).
+type GroupKey<'a> = (&'a Option<String>, &'a Option<String>, &'a Option<String>, bool);
+
+fn group_key(e: &ImageEntry) -> GroupKey<'_> {
+    (&e.ver.component, &e.ver.marker, &e.ver.branch, e.ver.describe.is_some())
+}
+
+#[derive(Default)]
 pub struct Images {
     // key: repository
-    pub entries: HashMap<String, HashSet<ImageEntry>>
+    pub entries: HashMap<String, HashSet<ImageEntry>>,
+    // key: repository
+    pub commits: HashMap<String, Vec<CommitEntry>>,
+    // key: image id. All listed tags including the ones not recognized as a version.
+    pub refs: HashMap<String, Vec<ImageRef>>,
+    // key: image id
+    pub created: HashMap<String, DateTime<Utc>>,
+    // key: image id. Untagged images. The tag of the value is '<none>'.
+    pub dangling: HashMap<String, ImageRef>,
+    // key: image id. Only available for inspected images.
+    pub labels: HashMap<String, label::Labels>,
 }
 
 impl Images {
-    pub fn delete<F>(&self, canonical_keep_count: usize, snapshot_keep_count: usize, mut del: F) -> ()
-      where F : FnMut(&str, &version::Version) -> () // repo, version
+    // Branches of the repository deleted by branch_active_since and max_branches. Activity of a branch is
+    // the creation time of its newest image. Branches whose creation time is unknown and branches not
+    // matching feature_branch are not deleted. Variants are not branches.
+    fn inactive_branches<'a>(&self, entry: &'a HashSet<ImageEntry>, policy: &KeepPolicy) -> HashSet<&'a str> {
+        let pattern = match &policy.feature_branch {
+            Some(pattern) => pattern,
+            None => return HashSet::new(),
+        };
+        // key: branch
+        let mut newest: HashMap<&str, DateTime<Utc>> = HashMap::new();
+        for e in entry {
+            if let (Some(branch), Some(created)) = (&e.ver.branch, self.created.get(&e.id)) {
+                if !pattern.matches(branch) {
+                    continue;
+                }
+                let n = newest.entry(branch).or_insert(*created);
+                if *n < *created {
+                    *n = *created;
+                }
+            }
+        }
+
+        let mut by_activity: Vec<(&str, DateTime<Utc>)> = newest.into_iter().collect();
+        by_activity.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        by_activity.into_iter().enumerate()
+            .filter(|(i, (_, created))| {
+                policy.branch_active_since.is_some_and(|since| *created < since) || policy.max_branches.is_some_and(|max| max <= *i)
+            })
+            .map(|(_, (branch, _))| branch)
+            .collect()
+    }
+
+    // Versions kept as a unit in each release group. Newest versions are kept in every repository of the
+    // group until the keep count of complete versions is reached. A version is complete if it exists in all of
+    // the repositories having its branch (snapshot marker, component). At most the keep count of incomplete
+    // versions are kept in a row so that a version missing in a repository does not keep older ones forever.
+    // key: repository
+    fn release_group_kept(&self, policy: &KeepPolicy) -> HashMap<&str, HashSet<&version::Version>> {
+        let mut kept: HashMap<&str, HashSet<&version::Version>> = HashMap::new();
+
+        for group in &policy.release_groups {
+            let repos: Vec<(&String, &HashSet<ImageEntry>)> = group.iter().filter_map(|r| self.entries.get_key_value(r)).collect();
+            // key: version, value: repositories having it
+            let mut sum: HashMap<GroupKey, HashMap<&version::Version, Vec<&str>>> = HashMap::new();
+            for (repo, entry) in &repos {
+                for e in entry.iter() {
+                    sum.entry(group_key(e)).or_default().entry(&e.ver).or_default().push(repo);
+                }
+            }
+
+            for versions in sum.values() {
+                let mut sorted: Vec<&&version::Version> = versions.keys().collect();
+                sorted.sort_by(|a, b| b.cmp(a));
+                let keep_count = repos.iter().map(|(repo, _)| policy.keep_count(repo, sorted[0])).max().unwrap_or(0);
+                let repo_count = versions.values().flatten().collect::<HashSet<_>>().len();
+
+                let mut complete = 0;
+                let mut incomplete = 0;
+                for ver in sorted {
+                    let is_complete = versions[ver].len() == repo_count;
+                    if keep_count <= complete {
+                        break;
+                    }
+                    // Older incomplete versions are not kept, but the walk goes on to the complete ones.
+                    if !is_complete && keep_count <= incomplete {
+                        continue;
+                    }
+                    for repo in &versions[ver] {
+                        kept.entry(repo).or_default().insert(ver);
+                    }
+                    if is_complete {
+                        complete += 1;
+                        incomplete = 0;
+                    } else {
+                        incomplete += 1;
+                    }
+                }
+            }
+        }
+        kept
+    }
+
+    // Commit tagged images are kept with the canonical keep count.
+    pub fn delete<F>(&self, policy: &KeepPolicy, mut del: F)
+      where F : FnMut(&str, &str, &str) // repo, tag, image id
     {
-        for (repo, entry) in &self.entries {
-            let mut sum_canonical: HashMap<&Option<String>, Vec<&version::Version>> = HashMap::new();
-            let mut sum_snapshot: HashMap<&Option<String>, Vec<&version::Version>> = HashMap::new();
+        let group_kept = self.release_group_kept(policy);
 
+        for (repo, entry) in &self.entries {
+            let mut sum: HashMap<GroupKey, Vec<&ImageEntry>> = HashMap::new();
             for e in entry {
-                let sum = if e.ver.is_snapshot { &mut sum_snapshot } else { &mut sum_canonical };
-                let keep_count = if e.ver.is_snapshot { snapshot_keep_count } else {canonical_keep_count };
-                let tbl = sum.entry(&e.ver.branch).or_insert_with(|| Vec::new());
-                match tbl.binary_search(&&e.ver) {
-                    Ok(_idx) => {
-                    },
-                    Err(idx) => {
-                        tbl.insert(idx, &e.ver);
+                sum.entry(group_key(e)).or_default().push(e);
+            }
+
+            let inactive = self.inactive_branches(entry, policy);
+            let kept_by_group = group_kept.get(repo.as_str());
+
+            for ((_component, marker, branch, is_describe), mut tbl) in sum {
+                // Newest first.
+                tbl.sort_by(|a, b| b.ver.cmp(&a.ver));
+                let is_stale = branch.as_ref().is_some_and(|b| policy.stale_branches.contains(b) || inactive.contains(b.as_str()));
+                let keep_count = if is_stale { 0 } else { policy.keep_count(repo, &tbl[0].ver) };
+                let kept_by_time = match (marker, &policy.buckets) {
+                    _ if is_stale || is_describe => HashSet::new(),
+                    (None, _) => policy.lines.kept(&tbl),
+                    (Some(_), Some(buckets)) => buckets.kept(&tbl, &self.created),
+                    (Some(_), None) => HashSet::new(),
+                };
+
+                for (i, v) in tbl.iter().enumerate() {
+                    let is_kept_by_group = !is_stale && kept_by_group.is_some_and(|kept| kept.contains(&v.ver));
+                    if i < keep_count || kept_by_time.contains(&i) || is_kept_by_group {
+                        continue;
+                    }
+                    del(repo, &v.ver.raw, &v.id);
+                    for a in &v.aliases {
+                        del(repo, &a.tag, &a.id);
                     }
                 }
+            }
+        }
+
+        for (repo, entry) in &self.commits {
+            let mut sorted: Vec<&CommitEntry> = entry.iter().collect();
+            sorted.sort_by(|a, b| a.created.cmp(&b.created).then_with(|| a.tag.cmp(&b.tag)));
 
-                if keep_count < tbl.len() {
-                    let v = tbl.remove(0);
-                    del(repo, v);
+            let keep_count = *policy.repositories.get(repo).unwrap_or(&policy.canonical);
+            let del_count = sorted.len().saturating_sub(keep_count);
+            for e in &sorted[..del_count] {
+                del(repo, &e.tag, &e.id);
+            }
+        }
+    }
+
+    // Version of the tag including aliases. None if the tag is not recognized as a version.
+    pub fn version_of(&self, repo: &str, tag: &str) -> Option<&version::Version> {
+        self.entries.get(repo)?.iter()
+            .find(|e| e.ver.raw == tag || e.aliases.iter().any(|a| a.tag == tag))
+            .map(|e| &e.ver)
+    }
+
+    // Every versioned and commit tag regardless of the keep policy.
+    pub fn each_tag<F>(&self, mut f: F)
+      where F : FnMut(&str, &str, &str, Option<&version::Version>) // repo, tag, image id, version
+    {
+        for (repo, entry) in &self.entries {
+            for e in entry {
+                f(repo, &e.ver.raw, &e.id, Some(&e.ver));
+                for a in &e.aliases {
+                    f(repo, &a.tag, &a.id, Some(&e.ver));
                 }
             }
         }
+
+        for (repo, entry) in &self.commits {
+            for e in entry {
+                f(repo, &e.tag, &e.id, None);
+            }
+        }
     }
 }
 
+// (image id, tag)
+#[cfg(test)]
+pub fn entries(parser: &version::VersionParser, tags: &[(&str, &str)]) -> HashSet<ImageEntry> {
+    tags.iter().map(|(id, tag)| ImageEntry { id: id.to_string(), ver: parser.parse(tag).unwrap(), aliases: Vec::new() }).collect()
+}
+
+#[cfg(test)]
+fn policy(canonical: usize, snapshot: usize) -> KeepPolicy {
+    KeepPolicy { canonical, snapshot, ..Default::default() }
+}
+
+// (repository, tags). Each tag is its own image.
+#[cfg(test)]
+fn images_of(parser: &version::VersionParser, repos: &[(&str, &[&str])]) -> Images {
+    let entries = repos.iter()
+        .map(|(repo, tags)| {
+            let tags: Vec<(&str, &str)> = tags.iter().map(|t| (*t, *t)).collect();
+            (repo.to_string(), entries(parser, &tags))
+        })
+        .collect();
+    Images { entries, ..Default::default() }
+}
+
+// Sorted 'repository:tag' deleted by the policy.
+#[cfg(test)]
+fn deleted_tags(images: &Images, policy: &KeepPolicy) -> Vec<String> {
+    let mut deleted: Vec<String> = Vec::new();
+    images.delete(policy, |repo, tag, _id| deleted.push(format!("{}:{}", repo, tag)));
+    deleted.sort();
+    deleted
+}
+
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn delete_test() {
     let parser = version::parser();
     let mut map: HashMap<String, HashSet<image::ImageEntry>> = HashMap::new();
     let mut entries0 = HashSet::new();
-    entries0.insert(ImageEntry { id: "id00".to_string(), ver: parser.parse("1.0").unwrap() });
-    entries0.insert(ImageEntry { id: "id01".to_string(), ver: parser.parse("1.1").unwrap() });
-    entries0.insert(ImageEntry { id: "id02".to_string(), ver: parser.parse("1.10").unwrap() });
-    entries0.insert(ImageEntry { id: "id03".to_string(), ver: parser.parse("1.2").unwrap() });
+    entries0.insert(ImageEntry { id: "id00".to_string(), ver: parser.parse("1.0").unwrap(), aliases: Vec::new() });
+    entries0.insert(ImageEntry { id: "id01".to_string(), ver: parser.parse("1.1").unwrap(), aliases: Vec::new() });
+    entries0.insert(ImageEntry { id: "id02".to_string(), ver: parser.parse("1.10").unwrap(), aliases: Vec::new() });
+    entries0.insert(ImageEntry { id: "id03".to_string(), ver: parser.parse("1.2").unwrap(), aliases: Vec::new() });
 
-    entries0.insert(ImageEntry { id: "id04".to_string(), ver: parser.parse("1.2-SNAPSHOT").unwrap() });
-    entries0.insert(ImageEntry { id: "id05".to_string(), ver: parser.parse("1.1-SNAPSHOT").unwrap() });
+    entries0.insert(ImageEntry { id: "id04".to_string(), ver: parser.parse("1.2-SNAPSHOT").unwrap(), aliases: Vec::new() });
+    entries0.insert(ImageEntry { id: "id05".to_string(), ver: parser.parse("1.1-SNAPSHOT").unwrap(), aliases: Vec::new() });
 
-    entries0.insert(ImageEntry { id: "id06".to_string(), ver: parser.parse("1.2.0-BR123").unwrap() });
-    entries0.insert(ImageEntry { id: "id07".to_string(), ver: parser.parse("1.2.1-BR123").unwrap() });
-    entries0.insert(ImageEntry { id: "id08".to_string(), ver: parser.parse("1.2.10-BR123").unwrap() });
-    entries0.insert(ImageEntry { id: "id09".to_string(), ver: parser.parse("1.2.2-BR123").unwrap() });
+    entries0.insert(ImageEntry { id: "id06".to_string(), ver: parser.parse("1.2.0-BR123").unwrap(), aliases: Vec::new() });
+    entries0.insert(ImageEntry { id: "id07".to_string(), ver: parser.parse("1.2.1-BR123").unwrap(), aliases: Vec::new() });
+    entries0.insert(ImageEntry { id: "id08".to_string(), ver: parser.parse("1.2.10-BR123").unwrap(), aliases: Vec::new() });
+    entries0.insert(ImageEntry { id: "id09".to_string(), ver: parser.parse("1.2.2-BR123").unwrap(), aliases: Vec::new() });
 
-    entries0.insert(ImageEntry { id: "id10".to_string(), ver: parser.parse("1.2.2-BR123-SNAPSHOT").unwrap() });
-    entries0.insert(ImageEntry { id: "id11".to_string(), ver: parser.parse("1.2.1-BR123-SNAPSHOT").unwrap() });
+    entries0.insert(ImageEntry { id: "id10".to_string(), ver: parser.parse("1.2.2-BR123-SNAPSHOT").unwrap(), aliases: Vec::new() });
+    entries0.insert(ImageEntry { id: "id11".to_string(), ver: parser.parse("1.2.1-BR123-SNAPSHOT").unwrap(), aliases: Vec::new() });
 
     map.insert("repo0".to_string(), entries0);
 
     let mut entries1 = HashSet::new();
-    entries1.insert(ImageEntry { id: "id12".to_string(), ver: parser.parse("2.0").unwrap() });
-    entries1.insert(ImageEntry { id: "id13".to_string(), ver: parser.parse("2.1").unwrap() });
-    entries1.insert(ImageEntry { id: "id14".to_string(), ver: parser.parse("2.10").unwrap() });
-    entries1.insert(ImageEntry { id: "id15".to_string(), ver: parser.parse("2.2").unwrap() });
+    entries1.insert(ImageEntry { id: "id12".to_string(), ver: parser.parse("2.0").unwrap(), aliases: Vec::new() });
+    entries1.insert(ImageEntry { id: "id13".to_string(), ver: parser.parse("2.1").unwrap(), aliases: Vec::new() });
+    entries1.insert(ImageEntry { id: "id14".to_string(), ver: parser.parse("2.10").unwrap(), aliases: Vec::new() });
+    entries1.insert(ImageEntry { id: "id15".to_string(), ver: parser.parse("2.2").unwrap(), aliases: Vec::new() });
 
     map.insert("repo1".to_string(), entries1);
 
-    let images = Images { entries: map };
+    let images = Images { entries: map, ..Default::default() };
 
     let mut deleted = HashSet::new();
-    images.delete(3, 1, |repo, ver| {
-        deleted.insert(format!("{}:{}", repo, ver.to_string()));
+    images.delete(&policy(3, 1), |repo, tag, _id| {
+        deleted.insert(format!("{}:{}", repo, tag));
     });
 
     assert_eq!(deleted.len(), 5);
@@ -113,3 +507,252 @@ fn delete_test() {
     assert_eq!(deleted.contains("repo0:1.2.1-BR123-SNAPSHOT"), true);
     assert_eq!(deleted.contains("repo1:2.0"), true);
 }
+
+#[test]
+fn delete_unique_snapshot_test() {
+    let parser = version::parser();
+    let images = images_of(&parser, &[("repo0", &["1.2", "1.2-SNAPSHOT", "1.3-20241018.123456-3", "1.3-20241018.123456-4"])]);
+
+    assert_eq!(deleted_tags(&images, &policy(3, 1)), vec!["repo0:1.2-SNAPSHOT", "repo0:1.3-20241018.123456-3"]);
+}
+
+#[cfg(test)]
+fn commit(id: &str, tag: &str, created: &str) -> CommitEntry {
+    CommitEntry { id: id.to_string(), tag: tag.to_string(), created: created.parse().unwrap() }
+}
+
+#[test]
+fn delete_commit_test() {
+    let mut commits: HashMap<String, Vec<CommitEntry>> = HashMap::new();
+    commits.insert("repo0".to_string(), vec![
+        commit("id00", "a1b2c3d", "2020-10-03T00:00:00Z"),
+        commit("id01", "0f0f0f0", "2020-10-01T00:00:00Z"),
+        commit("id02", "1234abc", "2020-10-04T00:00:00Z"),
+        commit("id03", "deadbee", "2020-10-02T00:00:00Z"),
+    ]);
+    commits.insert("repo1".to_string(), vec![
+        commit("id04", "abcdef0", "2020-10-01T00:00:00Z"),
+    ]);
+
+    let images = Images { commits, ..Default::default() };
+
+    let mut deleted = HashSet::new();
+    images.delete(&policy(2, 1), |repo, tag, _id| {
+        deleted.insert(format!("{}:{}", repo, tag));
+    });
+
+    assert_eq!(deleted.len(), 2);
+    assert!(deleted.contains("repo0:0f0f0f0"));
+    assert!(deleted.contains("repo0:deadbee"));
+}
+
+#[test]
+fn delete_marker_test() {
+    let parser = version::parser_with_markers(&[
+        version::parse_marker("-SNAPSHOT").unwrap(),
+        version::parse_marker("-dev:2").unwrap(),
+    ]).unwrap();
+    let mut map: HashMap<String, HashSet<image::ImageEntry>> = HashMap::new();
+    let entries0 = entries(&parser, &[
+        ("id00", "1.0-SNAPSHOT"), ("id01", "1.1-SNAPSHOT"), ("id02", "1.0-dev"), ("id03", "1.1-dev"), ("id04", "1.2-dev"),
+    ]);
+    map.insert("repo0".to_string(), entries0);
+
+    let images = Images { entries: map, ..Default::default() };
+    let mut keep = policy(3, 1);
+    keep.markers.insert("-dev".to_string(), 2);
+
+    let mut deleted = HashSet::new();
+    images.delete(&keep, |repo, tag, _id| {
+        deleted.insert(format!("{}:{}", repo, tag));
+    });
+
+    assert_eq!(deleted.len(), 2);
+    assert!(deleted.contains("repo0:1.0-SNAPSHOT"));
+    assert!(deleted.contains("repo0:1.0-dev"));
+}
+
+#[test]
+fn delete_alias_test() {
+    let parser = version::parser();
+    let mut map: HashMap<String, HashSet<image::ImageEntry>> = HashMap::new();
+    let mut entries0 = HashSet::new();
+    entries0.insert(ImageEntry {
+        id: "id00".to_string(), ver: parser.parse("1.0").unwrap(),
+        aliases: vec![Alias { id: "id00".to_string(), tag: "v1.0".to_string() }]
+    });
+    entries0.insert(ImageEntry {
+        id: "id01".to_string(), ver: parser.parse("v1.1").unwrap(),
+        aliases: vec![Alias { id: "id02".to_string(), tag: "1.1".to_string() }]
+    });
+    map.insert("repo0".to_string(), entries0);
+
+    let images = Images { entries: map, ..Default::default() };
+
+    let mut deleted = HashSet::new();
+    images.delete(&policy(1, 1), |repo, tag, _id| {
+        deleted.insert(format!("{}:{}", repo, tag));
+    });
+
+    assert_eq!(deleted.len(), 2);
+    assert!(deleted.contains("repo0:1.0"));
+    assert!(deleted.contains("repo0:v1.0"));
+}
+
+#[test]
+fn relaxed_test() {
+    let mut keep = policy(3, 1);
+    keep.markers.insert("-dev".to_string(), 5);
+
+    let relaxed = keep.relaxed(1, 1);
+    assert_eq!(relaxed.canonical, 2);
+    assert_eq!(relaxed.snapshot, 1);
+    assert_eq!(relaxed.markers["-dev"], 4);
+
+    let relaxed = keep.relaxed(10, 2);
+    assert_eq!(relaxed.canonical, 2);
+    assert_eq!(relaxed.snapshot, 1);
+    assert_eq!(relaxed.markers["-dev"], 2);
+    assert!(relaxed == keep.relaxed(11, 2));
+
+    keep.lines = LinePolicy { per_line: Some(3), lines: None };
+    assert_eq!(keep.relaxed(1, 1).lines, LinePolicy { per_line: Some(2), lines: None });
+}
+
+#[test]
+fn delete_repository_keep_count_test() {
+    let parser = version::parser();
+    let mut map: HashMap<String, HashSet<image::ImageEntry>> = HashMap::new();
+    for repo in &["repo0", "repo1"] {
+        map.insert(repo.to_string(), entries(&parser, &["1.0", "1.1", "1.2", "1.2-SNAPSHOT", "1.3-SNAPSHOT"].map(|v| (v, v))));
+    }
+    let images = Images { entries: map, ..Default::default() };
+
+    let mut keep = policy(1, 1);
+    keep.repositories.insert("repo1".to_string(), 2);
+    let mut deleted: Vec<String> = Vec::new();
+    images.delete(&keep, |repo, tag, _id| deleted.push(format!("{}:{}", repo, tag)));
+    deleted.sort();
+    assert_eq!(deleted, vec!["repo0:1.0", "repo0:1.1", "repo0:1.2-SNAPSHOT", "repo1:1.0"]);
+}
+
+#[test]
+fn delete_line_test() {
+    let parser = version::parser();
+    let images = images_of(&parser, &[("repo0", &["2.3.0", "2.3.1", "2.4.0", "2.5.0", "2.5.1", "2.5.2", "2.5.3", "2.5-SNAPSHOT", "2.4-SNAPSHOT"])]);
+    let deleted = |per_line: Option<usize>, lines: Option<usize>| {
+        let mut keep = policy(2, 1);
+        keep.lines = LinePolicy { per_line, lines };
+        deleted_tags(&images, &keep)
+    };
+
+    assert_eq!(deleted(None, None), vec!["repo0:2.3.0", "repo0:2.3.1", "repo0:2.4-SNAPSHOT", "repo0:2.4.0", "repo0:2.5.0", "repo0:2.5.1"]);
+    assert_eq!(deleted(Some(1), None), vec!["repo0:2.3.0", "repo0:2.4-SNAPSHOT", "repo0:2.5.0", "repo0:2.5.1"]);
+    assert_eq!(deleted(None, Some(2)), vec!["repo0:2.3.0", "repo0:2.3.1", "repo0:2.4-SNAPSHOT", "repo0:2.5.0", "repo0:2.5.1"]);
+    assert_eq!(deleted(Some(3), Some(2)), vec!["repo0:2.3.0", "repo0:2.3.1", "repo0:2.4-SNAPSHOT", "repo0:2.5.0"]);
+}
+
+#[test]
+fn delete_bucket_test() {
+    let parser = version::parser();
+    let builds = [
+        ("1.0.8-SNAPSHOT", "2020-10-18T10:00:00Z"), ("1.0.7-SNAPSHOT", "2020-10-18T08:00:00Z"),
+        ("1.0.6-SNAPSHOT", "2020-10-17T00:00:00Z"), ("1.0.5-SNAPSHOT", "2020-10-16T00:00:00Z"),
+        ("1.0.4-SNAPSHOT", "2020-10-10T00:00:00Z"), ("1.0.3-SNAPSHOT", "2020-10-06T00:00:00Z"),
+        ("1.0.2-SNAPSHOT", "2020-09-20T00:00:00Z"), ("1.0.1-SNAPSHOT", "2020-08-01T00:00:00Z"),
+    ];
+    let mut images = images_of(&parser, &[("repo0", &builds.map(|(ver, _)| ver))]);
+    images.created = builds.iter().map(|(ver, c)| (ver.to_string(), c.parse().unwrap())).collect();
+
+    let mut keep = policy(1, 0);
+    // 2020-10-18 is Sunday.
+    keep.buckets = Some(BucketPolicy { daily: 2, weekly: 2, monthly: 2, today: NaiveDate::from_ymd_opt(2020, 10, 18).unwrap() });
+    assert_eq!(deleted_tags(&images, &keep), vec!["repo0:1.0.1-SNAPSHOT", "repo0:1.0.3-SNAPSHOT", "repo0:1.0.5-SNAPSHOT", "repo0:1.0.7-SNAPSHOT"]);
+}
+
+#[test]
+fn delete_stale_branch_test() {
+    let parser = version::parser();
+    let images = images_of(&parser, &[("repo0", &["1.0-BR102", "1.1-BR102", "1.0-BR103", "1.0"])]);
+
+    let mut keep = policy(3, 1);
+    keep.stale_branches.insert("BR102".to_string());
+    assert_eq!(deleted_tags(&images, &keep), vec!["repo0:1.0-BR102", "repo0:1.1-BR102"]);
+}
+
+#[test]
+fn delete_inactive_branch_test() {
+    let parser = version::parser().with_variants(&["alpine".to_string()]);
+    let builds = [
+        ("1.0-BR101", "2020-09-01T00:00:00Z"), ("1.1-BR101-SNAPSHOT", "2020-09-02T00:00:00Z"),
+        ("1.0-BR102", "2020-10-01T00:00:00Z"), ("1.0-BR103", "2020-10-10T00:00:00Z"), ("1.0", "2020-01-01T00:00:00Z"),
+        ("8-sdk", "2020-08-01T00:00:00Z"), ("8-alpine", "2020-08-01T00:00:00Z"),
+    ];
+    let mut images = images_of(&parser, &[("repo0", &builds.map(|(ver, _)| ver))]);
+    images.created = builds.iter().map(|(ver, c)| (ver.to_string(), c.parse().unwrap())).collect();
+
+    let mut keep = policy(3, 1);
+    keep.feature_branch = Some(BranchPattern::parse(r"BR\d+").unwrap());
+    keep.branch_active_since = Some("2020-09-15T00:00:00Z".parse().unwrap());
+    assert_eq!(deleted_tags(&images, &keep), vec!["repo0:1.0-BR101", "repo0:1.1-BR101-SNAPSHOT"]);
+
+    let mut keep = policy(3, 1);
+    keep.feature_branch = Some(BranchPattern::parse(r"BR\d+").unwrap());
+    keep.max_branches = Some(1);
+    assert_eq!(deleted_tags(&images, &keep), vec!["repo0:1.0-BR101", "repo0:1.0-BR102", "repo0:1.1-BR101-SNAPSHOT"]);
+
+    // 'sdk' is not a feature branch.
+    let mut keep = policy(3, 1);
+    keep.feature_branch = Some(BranchPattern::parse(".+").unwrap());
+    keep.max_branches = Some(1);
+    assert_eq!(deleted_tags(&images, &keep), vec!["repo0:1.0-BR101", "repo0:1.0-BR102", "repo0:1.1-BR101-SNAPSHOT", "repo0:8-sdk"]);
+
+    let mut keep = policy(3, 1);
+    keep.max_branches = Some(1);
+    assert!(deleted_tags(&images, &keep).is_empty());
+}
+
+#[test]
+fn delete_component_test() {
+    let parser = version::parser().with_components(true);
+    let images = images_of(&parser, &[("repo0", &["api-1.2.3", "api-1.2.4", "worker-1.4.0", "web-v2.0", "web-v2.1", "1.0", "1.1"])]);
+
+    assert_eq!(deleted_tags(&images, &policy(1, 1)), vec!["repo0:1.0", "repo0:api-1.2.3", "repo0:web-v2.0"]);
+}
+
+#[test]
+fn delete_describe_test() {
+    let parser = version::parser();
+    let images = images_of(&parser, &[("repo0", &["1.2.2", "1.2.3", "1.2.3-2-gdef5678", "1.2.3-14-gabc1234", "1.2.3-9-g0123abc-dirty"])]);
+
+    let mut keep = policy(2, 1);
+    keep.describe = 1;
+    assert_eq!(deleted_tags(&images, &keep), vec!["repo0:1.2.3-2-gdef5678", "repo0:1.2.3-9-g0123abc-dirty"]);
+}
+
+#[test]
+fn delete_release_group_test() {
+    let parser = version::parser();
+    let images = images_of(&parser, &[
+        ("shop/api", &["1.7", "1.8", "1.9", "1.10"]), ("shop/worker", &["1.7", "1.8", "1.10", "1.11"]), ("other", &["1.7", "1.8", "1.9"]),
+    ]);
+
+    let mut keep = policy(2, 1);
+    keep.release_groups.push(vec!["shop/api".to_string(), "shop/worker".to_string(), "shop/web".to_string()]);
+    // 1.10 and 1.8 are the newest 2 versions existing in both repositories.
+    assert_eq!(deleted_tags(&images, &keep), vec!["other:1.7", "shop/api:1.7", "shop/worker:1.7"]);
+}
+
+#[test]
+fn delete_release_group_partial_test() {
+    let parser = version::parser();
+    let images = images_of(&parser, &[
+        ("shop/api", &["1.1", "1.2", "1.3", "1.4", "1.5", "1.0-BR102", "1.1-BR102", "1.2-BR102"]), ("shop/worker", &["1.1"]),
+    ]);
+
+    let mut keep = policy(2, 1);
+    keep.release_groups.push(vec!["shop/api".to_string(), "shop/worker".to_string()]);
+    // BR102 exists only in shop/api, so its versions are complete. Only 2 versions missing in shop/worker are kept in a row
+    // until the complete 1.1.
+    assert_eq!(deleted_tags(&images, &keep), vec!["shop/api:1.0-BR102", "shop/api:1.2", "shop/api:1.3"]);
+}
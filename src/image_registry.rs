@@ -1,37 +1,91 @@
-use std::process::{Command, Output};
+use std::process::Command;
 use std::io::{BufRead, BufReader};
 use std::collections::HashMap;
 use std::collections::HashSet;
 
+use chrono::{DateTime, TimeZone, Utc};
+use regex::Regex;
+
 use super::version;
 use super::image;
+use super::space;
+use super::label;
 
 pub trait ImageRegistry {
-    fn list(&self) -> image::Images;
-    fn remove(&self, image_name: &str, ver: &version::Version, is_dryrun: bool) -> ();
+    // Tags that have no version but match commit_tag are listed as commit entries.
+    fn list(&self, ver_parser: &version::VersionParser, commit_tag: Option<&Regex>) -> image::Images;
+    // Err has the message if the command fails.
+    fn remove(&self, image_name: &str, tag: &str, is_dryrun: bool) -> Result<(), String>;
+    // Remove the image itself with all of its tags.
+    fn remove_image(&self, id: &str, refs: &[image::ImageRef], is_dryrun: bool) -> Result<(), String>;
+    // Point the target tag at the image of the source tag.
+    fn tag(&self, source: &image::ImageRef, target: &image::ImageRef, is_dryrun: bool) -> Result<(), String>;
+
+    // key: image id. Images whose size cannot be obtained are not included.
+    fn inspect(&self, _ids: &[&str]) -> HashMap<String, space::ImageInfo> {
+        HashMap::new()
+    }
+
+    // Usage of the filesystem storing images. None if it cannot be obtained.
+    fn disk_usage(&self) -> Option<space::DiskUsage> {
+        None
+    }
 }
 
 pub struct DockerImageRegistry {
+    // Global options such as '--host ssh://user@builder' or '--context builder'.
+    global_args: Vec<String>,
 }
 
 pub struct IbmCloudRegistry {
 }
 
-pub const DOCKER_IMAGE_REGISTRY: DockerImageRegistry = DockerImageRegistry {};
-
 pub const IBM_CLOUD_REGISTRY: IbmCloudRegistry = IbmCloudRegistry {};
 
+// Tag of untagged images in the list.
+const NO_TAG: &str = "<none>";
+
+// Short or full git commit SHA.
+pub const DEFAULT_COMMIT_TAG_PATTERN: &str = "^[0-9a-f]{7,40}$";
+
+impl DockerImageRegistry {
+    // host and context are passed to docker so that a remote docker engine can be pruned.
+    pub fn new(host: Option<&str>, context: Option<&str>) -> DockerImageRegistry {
+        let mut global_args = Vec::new();
+        if let Some(h) = host {
+            global_args.push("--host".to_string());
+            global_args.push(h.to_string());
+        }
+        if let Some(c) = context {
+            global_args.push("--context".to_string());
+            global_args.push(c.to_string());
+        }
+        DockerImageRegistry { global_args }
+    }
+
+    fn docker(&self) -> Command {
+        let mut cmd = Command::new("docker");
+        cmd.args(&self.global_args);
+        cmd
+    }
+
+    // Shown in dry run.
+    fn program(&self) -> String {
+        [&["docker".to_string()], self.global_args.as_slice()].concat().join(" ")
+    }
+}
+
 impl ImageRegistry for DockerImageRegistry {
-    fn list(&self) -> image::Images {
-        let out = Command::new("docker")
+    fn list(&self, ver_parser: &version::VersionParser, commit_tag: Option<&Regex>) -> image::Images {
+        let out = self.docker()
             .arg("images")
             .arg("--format")
-            .arg("{{.ID}}\t{{.Repository}}\t{{.Tag}}")
+            .arg("{{.ID}}\t{{.Repository}}\t{{.Tag}}\t{{.CreatedAt}}")
             .output()
             .expect("Cannot run 'docker images'. Please check docker installation.");
 
         if out.status.success() {
-            parse_docker_image_list(out)
+            parse_docker_image_list(&out.stdout, ver_parser, commit_tag, parse_docker_created_at)
         } else {
             println!("stdout: {}", String::from_utf8_lossy(&out.stdout));
             println!("stderr: {}", String::from_utf8_lossy(&out.stderr));
@@ -39,39 +93,160 @@ impl ImageRegistry for DockerImageRegistry {
         }
     }
     
-    fn remove(&self, image_name: &str, ver: &version::Version, is_dryrun: bool) -> () {
-        let img = format!("{}:{}", image_name, ver);
+    fn remove(&self, image_name: &str, tag: &str, is_dryrun: bool) -> Result<(), String> {
+        let img = format!("{}:{}", image_name, tag);
 
         if is_dryrun {
-            println!("docker rmi {}", img);
+            println!("{} rmi {}", self.program(), img);
         } else {
-            let out = Command::new("docker")
+            let out = self.docker()
                 .arg("rmi")
                 .arg(&img)
                 .output()
                 .expect("Cannot run 'docker rmi {}'. Please check docker installation.");
 
             if ! out.status.success() {
-                println!("stdout: {}", String::from_utf8_lossy(&out.stdout));
-                println!("stderr: {}", String::from_utf8_lossy(&out.stderr));
-                panic!("docker rmi {} fails with status {}.", img, out.status);
+                return Result::Err(format!(
+                    "docker rmi {} fails with status {}. stdout: {} stderr: {}", img, out.status,
+                    String::from_utf8_lossy(&out.stdout), String::from_utf8_lossy(&out.stderr)
+                ));
+            }
+        }
+
+        Result::Ok(())
+    }
+
+    // 'docker rmi [id]' without '-f' removes all the tags of one repository and keeps the image if it gets
+    // a container in the meantime. It refuses an image referenced in several repositories, so the tags of
+    // the other repositories are removed one by one beforehand.
+    fn remove_image(&self, id: &str, refs: &[image::ImageRef], is_dryrun: bool) -> Result<(), String> {
+        for r in other_repository_refs(refs) {
+            self.remove(&r.repo, &r.tag, is_dryrun)?;
+        }
+
+        if is_dryrun {
+            println!("{} rmi {}", self.program(), id);
+        } else {
+            let out = self.docker()
+                .arg("rmi")
+                .arg(id)
+                .output()
+                .expect("Cannot run 'docker rmi'. Please check docker installation.");
+
+            if ! out.status.success() {
+                return Result::Err(format!(
+                    "docker rmi {} fails with status {}. stdout: {} stderr: {}", id, out.status,
+                    String::from_utf8_lossy(&out.stdout), String::from_utf8_lossy(&out.stderr)
+                ));
+            }
+        }
+
+        Result::Ok(())
+    }
+
+    fn inspect(&self, ids: &[&str]) -> HashMap<String, space::ImageInfo> {
+        if ids.is_empty() {
+            return HashMap::new();
+        }
+
+        let out = self.docker()
+            .arg("image")
+            .arg("inspect")
+            .arg("--format")
+            .arg(docker_image_inspect_format())
+            .args(ids)
+            .output()
+            .expect("Cannot run 'docker image inspect'. Please check docker installation.");
+
+        // An image removed in the meantime fails the command, but the others are still printed.
+        if ! out.status.success() {
+            println!("docker image inspect fails with status {}. Sizes of the images not inspected are unknown. stderr: {}",
+                     out.status, String::from_utf8_lossy(&out.stderr));
+        }
+        parse_docker_image_inspect(ids, &String::from_utf8_lossy(&out.stdout))
+    }
+
+    fn tag(&self, source: &image::ImageRef, target: &image::ImageRef, is_dryrun: bool) -> Result<(), String> {
+        if is_dryrun {
+            println!("{} tag {} {}", self.program(), source, target);
+        } else {
+            let out = self.docker()
+                .arg("tag")
+                .arg(source.to_string())
+                .arg(target.to_string())
+                .output()
+                .expect("Cannot run 'docker tag'. Please check docker installation.");
+
+            if ! out.status.success() {
+                return Result::Err(format!(
+                    "docker tag {} {} fails with status {}. stdout: {} stderr: {}", source, target, out.status,
+                    String::from_utf8_lossy(&out.stdout), String::from_utf8_lossy(&out.stderr)
+                ));
+            }
+        }
+
+        Result::Ok(())
+    }
+
+    // Docker Desktop stores images in a VM. In this case df fails and None is returned.
+    // The local df cannot see the filesystem of a remote docker engine, so None is returned for it too
+    // including the one selected by DOCKER_HOST or the current docker context.
+    fn disk_usage(&self) -> Option<space::DiskUsage> {
+        if !self.global_args.is_empty() {
+            return None;
+        }
+        if let Result::Ok(host) = std::env::var("DOCKER_HOST") {
+            if !is_local_endpoint(&host) {
+                return None;
             }
         }
+        // Older docker without contexts fails, and the engine is regarded as local.
+        let context = self.docker()
+            .arg("context")
+            .arg("inspect")
+            .arg("--format")
+            .arg("{{.Endpoints.docker.Host}}")
+            .output()
+            .ok()
+            .filter(|out| out.status.success());
+        if let Some(out) = context {
+            if !is_local_endpoint(String::from_utf8_lossy(&out.stdout).trim()) {
+                return None;
+            }
+        }
+
+        let info = self.docker()
+            .arg("info")
+            .arg("--format")
+            .arg("{{.DockerRootDir}}")
+            .output()
+            .ok()
+            .filter(|out| out.status.success())?;
+        let root_dir = String::from_utf8_lossy(&info.stdout).trim().to_string();
+
+        let df = Command::new("df")
+            .arg("-Pk")
+            .arg(&root_dir)
+            .output()
+            .ok()
+            .filter(|out| out.status.success())?;
+
+        space::parse_df(&String::from_utf8_lossy(&df.stdout))
     }
 }
 
 impl ImageRegistry for IbmCloudRegistry {
-    fn list(&self) -> image::Images {
+    fn list(&self, ver_parser: &version::VersionParser, commit_tag: Option<&Regex>) -> image::Images {
         let out = Command::new("ibmcloud")
             .arg("cr")
             .arg("images")
             .arg("--format")
-            .arg("{{.Digest}}\t{{.Repository}}\t{{.Tag}}")
+            .arg("{{.Digest}}\t{{.Repository}}\t{{.Tag}}\t{{.Created}}")
             .output()
             .expect("Cannot run 'ibmcloud cr images'. Please check ibmcloud CLI installation.");
 
         if out.status.success() {
-            parse_docker_image_list(out)
+            parse_docker_image_list(&out.stdout, ver_parser, commit_tag, parse_ibmcloud_created)
         } else {
             println!("stdout: {}", String::from_utf8_lossy(&out.stdout));
             println!("stderr: {}", String::from_utf8_lossy(&out.stderr));
@@ -79,8 +254,8 @@ impl ImageRegistry for IbmCloudRegistry {
         }
     }
 
-    fn remove(&self, image_name: &str, ver: &version::Version, is_dryrun: bool) -> () {
-        let img = format!("{}:{}", image_name, ver);
+    fn remove(&self, image_name: &str, tag: &str, is_dryrun: bool) -> Result<(), String> {
+        let img = format!("{}:{}", image_name, tag);
 
         if is_dryrun {
             println!("ibmcloud cr image-rm {}", img);
@@ -93,35 +268,212 @@ impl ImageRegistry for IbmCloudRegistry {
                 .expect("Cannot run 'ibmcloud cr image-rm'. Please check ibmcloud CLI installation.");
 
             if ! out.status.success() {
-                println!("stdout: {}", String::from_utf8_lossy(&out.stdout));
-                println!("stderr: {}", String::from_utf8_lossy(&out.stderr));
-                panic!("ibmcloud image-rm {} fails with status {}.", img, out.status);
+                return Result::Err(format!(
+                    "ibmcloud image-rm {} fails with status {}. stdout: {} stderr: {}", img, out.status,
+                    String::from_utf8_lossy(&out.stdout), String::from_utf8_lossy(&out.stderr)
+                ));
             }
         }
+
+        Result::Ok(())
     }
+
+    // The id is the digest. Digests are per repository in the registry.
+    fn remove_image(&self, id: &str, refs: &[image::ImageRef], is_dryrun: bool) -> Result<(), String> {
+        let mut repos: Vec<&str> = refs.iter().map(|r| r.repo.as_str()).collect();
+        repos.sort_unstable();
+        repos.dedup();
+
+        for repo in repos {
+            let img = format!("{}@{}", repo, id);
+
+            if is_dryrun {
+                println!("ibmcloud cr image-rm {}", img);
+            } else {
+                let out = Command::new("ibmcloud")
+                    .arg("cr")
+                    .arg("image-rm")
+                    .arg(&img)
+                    .output()
+                    .expect("Cannot run 'ibmcloud cr image-rm'. Please check ibmcloud CLI installation.");
+
+                if ! out.status.success() {
+                    return Result::Err(format!(
+                        "ibmcloud image-rm {} fails with status {}. stdout: {} stderr: {}", img, out.status,
+                        String::from_utf8_lossy(&out.stdout), String::from_utf8_lossy(&out.stderr)
+                    ));
+                }
+            }
+        }
+
+        Result::Ok(())
+    }
+
+    fn tag(&self, source: &image::ImageRef, target: &image::ImageRef, is_dryrun: bool) -> Result<(), String> {
+        if is_dryrun {
+            println!("ibmcloud cr image-tag {} {}", source, target);
+        } else {
+            let out = Command::new("ibmcloud")
+                .arg("cr")
+                .arg("image-tag")
+                .arg(source.to_string())
+                .arg(target.to_string())
+                .output()
+                .expect("Cannot run 'ibmcloud cr image-tag'. Please check ibmcloud CLI installation.");
+
+            if ! out.status.success() {
+                return Result::Err(format!(
+                    "ibmcloud image-tag {} {} fails with status {}. stdout: {} stderr: {}", source, target, out.status,
+                    String::from_utf8_lossy(&out.stdout), String::from_utf8_lossy(&out.stderr)
+                ));
+            }
+        }
+
+        Result::Ok(())
+    }
+}
+
+// One line per id in the same order as ids. Labels follow the layers as tab separated 'key=value'.
+// Id, parent, size, layers and the value of each label::KEYS. Label values are printed as JSON strings
+// so that a tab or newline in them does not break the line.
+fn docker_image_inspect_format() -> String {
+    let labels: String = label::KEYS.iter()
+        .map(|k| format!("\t{{{{json (index .Config.Labels \"{}\")}}}}", k))
+        .collect();
+    format!("{{{{.Id}}}}\t{{{{.Parent}}}}\t{{{{.Size}}}}\t{{{{range .RootFS.Layers}}}}{{{{.}}}} {{{{end}}}}{}", labels)
+}
+
+// Endpoint of a docker engine on this machine such as 'unix:///var/run/docker.sock'. Empty means the default.
+// Tags outside of the first repository of the image. Untagged references are skipped.
+fn other_repository_refs(refs: &[image::ImageRef]) -> Vec<&image::ImageRef> {
+    let tagged: Vec<&image::ImageRef> = refs.iter().filter(|r| r.tag != NO_TAG).collect();
+    match tagged.first() {
+        Some(first) => tagged.iter().filter(|r| r.repo != first.repo).cloned().collect(),
+        None => Vec::new(),
+    }
+}
+
+fn is_local_endpoint(host: &str) -> bool {
+    host.is_empty() || host.starts_with("unix://") || host.starts_with("npipe://")
+}
+
+// Lines are matched to ids by the full id since images failed to inspect are not printed.
+fn parse_docker_image_inspect(ids: &[&str], out: &str) -> HashMap<String, space::ImageInfo> {
+    let mut infos = HashMap::new();
+
+    for line in out.lines() {
+        let mut z = line.split('\t');
+        let full_id = z.next().unwrap_or("").to_string();
+        let id = match ids.iter().find(|id| full_id.trim_start_matches("sha256:").starts_with(id.trim_start_matches("sha256:"))) {
+            None => continue,
+            Some(id) => id,
+        };
+        let parent = z.next().filter(|p| !p.is_empty()).map(|p| p.to_string());
+        match z.next().and_then(|size| size.parse().ok()) {
+            None => println!("Size of image {} is unrecognized: {}", id, line),
+            Some(size) => {
+                let layers = z.next().unwrap_or("").split_whitespace().map(|l| l.to_string()).collect();
+                // Missing labels are printed as "".
+                let labels = label::KEYS.iter().zip(z)
+                    .filter_map(|(k, v)| v.strip_prefix('"').and_then(|v| v.strip_suffix('"')).map(|v| (k.to_string(), v.to_string())))
+                    .filter(|(_, v)| !v.is_empty())
+                    .collect();
+                infos.insert(id.to_string(), space::ImageInfo {full_id, parent, size, layers, labels});
+            }
+        }
+    }
+
+    infos
+}
+
+// Docker prints CreatedAt like '2020-10-18 12:34:56 +0900 JST'.
+fn parse_docker_created_at(s: &str) -> Option<DateTime<Utc>> {
+    let without_zone_name: Vec<&str> = s.split(' ').take(3).collect();
+    DateTime::parse_from_str(&without_zone_name.join(" "), "%Y-%m-%d %H:%M:%S %z")
+        .ok().map(|t| t.with_timezone(&Utc))
+}
+
+// IBM Cloud Registry prints Created as unix time in seconds.
+fn parse_ibmcloud_created(s: &str) -> Option<DateTime<Utc>> {
+    s.parse().ok().and_then(|secs| Utc.timestamp_opt(secs, 0).single())
 }
 
-fn parse_docker_image_list(out: Output) -> image::Images {
-    let br = BufReader::new(out.stdout.as_slice());
+fn parse_docker_image_list(
+    stdout: &[u8], ver_parser: &version::VersionParser, commit_tag: Option<&Regex>,
+    parse_created: fn(&str) -> Option<DateTime<Utc>>
+) -> image::Images {
+    let br = BufReader::new(stdout);
     let mut hash: HashMap<String, HashSet<image::ImageEntry>> = HashMap::new();
-    let ver_parser = version::parser();
-        
-    for (_, line) in br.lines().enumerate() {
+    let mut commits: HashMap<String, Vec<image::CommitEntry>> = HashMap::new();
+    let mut refs: HashMap<String, Vec<image::ImageRef>> = HashMap::new();
+    let mut created_by_id: HashMap<String, DateTime<Utc>> = HashMap::new();
+    let mut dangling: HashMap<String, image::ImageRef> = HashMap::new();
+
+    for line in br.lines() {
         let l = line.unwrap();
         let mut z = l.split('\t');
         let id = z.next().unwrap();
         let repository = z.next().unwrap();
         let tag = z.next().unwrap();
-        match ver_parser.parse(tag) {
-            None => println!("Version(={}) is unrecognized ignored: {}", tag, l),
+        let created = z.next().unwrap_or("");
+        if let Some(t) = parse_created(created) {
+            created_by_id.insert(id.to_string(), t);
+        }
+
+        // Dangling images have no tags, but have an entry in refs so that they can be inspected.
+        // An image listed as 'repo:<none>' such as the one pulled by digest is not dangling.
+        let image_refs = refs.entry(id.to_string()).or_default();
+        if tag == NO_TAG {
+            if repository == NO_TAG {
+                dangling.insert(id.to_string(), image::ImageRef {repo: repository.to_string(), tag: tag.to_string()});
+            }
+            continue;
+        }
+        image_refs.push(image::ImageRef {repo: repository.to_string(), tag: tag.to_string()});
+
+        // A commit SHA made only of digits such as '1234567' would be a build number otherwise.
+        let is_digit_commit = tag.bytes().all(|b| b.is_ascii_digit()) && commit_tag.is_some_and(|re| re.is_match(tag));
+        let ver = if is_digit_commit { None } else { ver_parser.parse(tag) };
+
+        match ver {
+            None => match commit_tag.filter(|re| re.is_match(tag)) {
+                None => println!("Version(={}) is unrecognized ignored: {}", tag, l),
+                Some(_) => match parse_created(created) {
+                    None => println!("Created(={}) is unrecognized ignored: {}", created, l),
+                    Some(created) => {
+                        commits.entry(repository.to_string()).or_default().push(
+                            image::CommitEntry {id: id.to_string(), tag: tag.to_string(), created}
+                        );
+                    }
+                }
+            },
             Some(ver) => {
+                let mut e = image::ImageEntry {id: id.to_string(), ver, aliases: Vec::new()};
                 match hash.get_mut(repository) {
-                    Some(entry) => {
-                        entry.insert(image::ImageEntry {id: id.to_string(), ver: ver});
+                    Some(entry) => match entry.take(&e) {
+                        // The tag without a variant represents the version so that it is promoted.
+                        Some(existing) if existing.ver.variant.is_some() && e.ver.variant.is_none() => {
+                            println!("Tag {} is a variant of {} in {}. They are kept or deleted together.", existing.ver, tag, repository);
+                            e.aliases = existing.aliases;
+                            e.aliases.push(image::Alias {id: existing.id, tag: existing.ver.raw});
+                            entry.insert(e);
+                        },
+                        Some(mut existing) => {
+                            if e.ver.variant.is_some() || existing.ver.variant.is_some() {
+                                println!("Tag {} is a variant of {} in {}. They are kept or deleted together.", tag, existing.ver, repository);
+                            } else {
+                                println!("Tag {} is an alias of {} in {}. They are kept or deleted together.", tag, existing.ver, repository);
+                            }
+                            existing.aliases.push(image::Alias {id: id.to_string(), tag: tag.to_string()});
+                            entry.insert(existing);
+                        },
+                        None => {
+                            entry.insert(e);
+                        }
                     },
                     None => {
                         let mut set = HashSet::new();
-                        set.insert(image::ImageEntry {id: id.to_string(), ver: ver});
+                        set.insert(e);
                         hash.insert(repository.to_string(), set);
                     }
                 }
@@ -129,5 +481,142 @@ fn parse_docker_image_list(out: Output) -> image::Images {
         }
     }
         
-    image::Images {entries: hash}
+    image::Images {entries: hash, commits, refs, created: created_by_id, dangling, labels: HashMap::new()}
+}
+
+#[test]
+fn parse_docker_created_at_test() {
+    assert_eq!(
+        parse_docker_created_at("2020-10-18 12:34:56 +0900 JST"),
+        Some("2020-10-18T03:34:56Z".parse().unwrap())
+    );
+    assert_eq!(parse_docker_created_at("N/A"), None);
+}
+
+#[test]
+fn parse_commit_tag_test() {
+    let stdout = b"id0\trepo0\t1.0\t2020-10-18 12:34:56 +0900 JST\n\
+                   id1\trepo0\ta1b2c3d\t2020-10-18 12:34:56 +0900 JST\n\
+                   id2\trepo0\tlatest\t2020-10-18 12:34:56 +0900 JST\n\
+                   id3\trepo0\t1234567\t2020-10-18 12:34:56 +0900 JST\n";
+
+    let images = parse_docker_image_list(stdout, &version::parser(), None, parse_docker_created_at);
+    assert_eq!(images.entries["repo0"].len(), 2);
+    assert!(images.commits.is_empty());
+
+    let re = Regex::new(DEFAULT_COMMIT_TAG_PATTERN).unwrap();
+    let images = parse_docker_image_list(stdout, &version::parser(), Some(&re), parse_docker_created_at);
+    assert_eq!(images.entries["repo0"].len(), 1);
+    assert_eq!(images.commits["repo0"].len(), 2);
+    assert!(images.commits["repo0"].iter().any(|c| c.tag == "a1b2c3d"));
+    assert!(images.commits["repo0"].iter().any(|c| c.tag == "1234567"));
+}
+
+#[test]
+fn parse_alias_test() {
+    let stdout = b"id0\trepo0\t1.2\t2020-10-18 12:34:56 +0900 JST\n\
+                   id0\trepo0\tv1.2\t2020-10-18 12:34:56 +0900 JST\n\
+                   id1\trepo0\t1.3\t2020-10-18 12:34:56 +0900 JST\n";
+
+    let images = parse_docker_image_list(stdout, &version::parser(), None, parse_docker_created_at);
+    let entries = &images.entries["repo0"];
+    assert_eq!(entries.len(), 2);
+
+    let e = entries.iter().find(|e| e.ver.raw == "1.2").unwrap();
+    assert_eq!(e.id, "id0");
+    assert_eq!(e.aliases.len(), 1);
+    assert_eq!(e.aliases[0].tag, "v1.2");
+}
+
+#[test]
+fn parse_variant_test() {
+    let stdout = b"id1\trepo0\t1.2-alpine\t2020-10-18 12:34:56 +0900 JST\n\
+                   id0\trepo0\t1.2\t2020-10-18 12:34:56 +0900 JST\n\
+                   id2\trepo0\t1.3-alpine\t2020-10-18 12:34:56 +0900 JST\n";
+
+    let parser = version::parser().with_variants(&["alpine".to_string()]);
+    let images = parse_docker_image_list(stdout, &parser, None, parse_docker_created_at);
+    let entries = &images.entries["repo0"];
+    assert_eq!(entries.len(), 2);
+
+    let e = entries.iter().find(|e| e.ver.raw == "1.2").unwrap();
+    assert_eq!(e.aliases.len(), 1);
+    assert_eq!(e.aliases[0].id, "id1");
+    assert_eq!(e.aliases[0].tag, "1.2-alpine");
+}
+
+#[test]
+fn parse_docker_image_inspect_test() {
+    let out = "sha256:id0\t\t130\tsha256:a sha256:b \t\"\"\t\"\"\t\"\"\n\
+               sha256:id1\tsha256:id0\t150\tsha256:a sha256:b sha256:c \t\"\"\t\"10\"\t\"2024-12-31\"\n";
+    let infos = parse_docker_image_inspect(&["id1", "id2", "id0"], out);
+    assert_eq!(infos.len(), 2);
+    assert_eq!(infos["id0"].full_id, "sha256:id0");
+    assert_eq!(infos["id0"].parent, None);
+    assert_eq!(infos["id1"].parent, Some("sha256:id0".to_string()));
+    assert_eq!(infos["id0"].size, 130);
+    assert_eq!(infos["id0"].layers, vec!["sha256:a", "sha256:b"]);
+    assert_eq!(infos["id1"].size, 150);
+    assert_eq!(infos["id1"].layers.len(), 3);
+    assert!(infos["id0"].labels.is_empty());
+    assert_eq!(infos["id1"].labels.len(), 2);
+    assert_eq!(infos["id1"].labels["io.ruimo.keep"], "10");
+    assert_eq!(infos["id1"].labels["io.ruimo.expires"], "2024-12-31");
+}
+
+#[test]
+fn docker_image_inspect_format_test() {
+    assert_eq!(
+        docker_image_inspect_format(),
+        "{{.Id}}\t{{.Parent}}\t{{.Size}}\t{{range .RootFS.Layers}}{{.}} {{end}}\
+         \t{{json (index .Config.Labels \"io.ruimo.retain\")}}\
+         \t{{json (index .Config.Labels \"io.ruimo.keep\")}}\
+         \t{{json (index .Config.Labels \"io.ruimo.expires\")}}"
+    );
+}
+
+#[test]
+fn parse_dangling_test() {
+    let stdout = b"id0\trepo0\t1.2\t2020-10-18 12:34:56 +0900 JST\n\
+                   id1\t<none>\t<none>\t2020-10-18 12:34:56 +0900 JST\n\
+                   id2\trepo0\t<none>\t2020-10-18 12:34:56 +0900 JST\n";
+
+    let images = parse_docker_image_list(stdout, &version::parser(), None, parse_docker_created_at);
+    assert_eq!(images.entries["repo0"].len(), 1);
+    assert_eq!(images.dangling.len(), 1);
+    assert!(!images.dangling.contains_key("id2"));
+    assert_eq!(images.dangling["id1"].to_string(), "<none>:<none>");
+    assert!(images.refs["id1"].is_empty());
+    assert!(images.created.contains_key("id1"));
+}
+
+#[test]
+fn is_local_endpoint_test() {
+    assert!(is_local_endpoint(""));
+    assert!(is_local_endpoint("unix:///var/run/docker.sock"));
+    assert!(!is_local_endpoint("ssh://user@builder"));
+    assert!(!is_local_endpoint("tcp://builder:2376"));
+}
+
+#[test]
+fn docker_global_args_test() {
+    assert_eq!(DockerImageRegistry::new(None, None).program(), "docker");
+    assert_eq!(DockerImageRegistry::new(Some("ssh://user@builder"), None).program(), "docker --host ssh://user@builder");
+    assert_eq!(DockerImageRegistry::new(None, Some("builder")).program(), "docker --context builder");
+}
+
+#[test]
+fn other_repository_refs_test() {
+    let refs = vec![
+        image::ImageRef { repo: "repo0".to_string(), tag: "1.2".to_string() },
+        image::ImageRef { repo: "repo0".to_string(), tag: "stable".to_string() },
+        image::ImageRef { repo: "mirror/repo0".to_string(), tag: "1.2".to_string() },
+    ];
+    let others: Vec<String> = other_repository_refs(&refs).iter().map(|r| r.to_string()).collect();
+    assert_eq!(others, vec!["mirror/repo0:1.2"]);
+
+    assert!(other_repository_refs(&refs[..2]).is_empty());
+
+    let dangling = vec![image::ImageRef { repo: NO_TAG.to_string(), tag: NO_TAG.to_string() }];
+    assert!(other_repository_refs(&dangling).is_empty());
 }
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::image;
+use super::space;
+
+pub const RETAIN: &str = "io.ruimo.retain";
+pub const KEEP: &str = "io.ruimo.keep";
+pub const EXPIRES: &str = "io.ruimo.expires";
+pub const KEYS: [&str; 3] = [RETAIN, KEEP, EXPIRES];
+
+// Retention declared by image labels. Other labels are ignored.
+#[derive(Default, Debug, PartialEq)]
+pub struct Labels {
+    // io.ruimo.retain=true: The image is never deleted.
+    pub retain: bool,
+    // io.ruimo.keep=10: Keep count of the repository of the image.
+    pub keep: Option<usize>,
+    // io.ruimo.expires=2024-12-31: The image is kept until the date and deleted after it.
+    pub expires: Option<NaiveDate>,
+}
+
+impl Labels {
+    pub fn parse(id: &str, labels: &HashMap<String, String>) -> Labels {
+        let mut parsed = Labels::default();
+        for (key, value) in labels {
+            let is_valid = match key.as_str() {
+                RETAIN => match value.to_lowercase().as_str() {
+                    "true" => { parsed.retain = true; true },
+                    "false" => true,
+                    _ => false,
+                },
+                KEEP => value.parse().map(|count| parsed.keep = Some(count)).is_ok(),
+                EXPIRES => NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|date| parsed.expires = Some(date)).is_ok(),
+                _ => true,
+            };
+            if !is_valid {
+                println!("Label {}={} of image {} is unrecognized ignored.", key, value, id);
+            }
+        }
+        parsed
+    }
+
+    pub fn is_expired(&self, today: NaiveDate) -> bool {
+        self.expires.is_some_and(|date| date < today)
+    }
+}
+
+// key: image id
+pub fn from_infos(infos: &HashMap<String, space::ImageInfo>) -> HashMap<String, Labels> {
+    infos.iter().map(|(id, info)| (id.clone(), Labels::parse(id, &info.labels))).collect()
+}
+
+// Keep count of each repository declared by io.ruimo.keep. If images of a repository have
+// different counts, the one of the newest image wins. key: repository
+pub fn repository_keep_counts(images: &image::Images) -> HashMap<String, usize> {
+    // key: repository, value: (created, count)
+    let mut counts: HashMap<&str, (Option<&DateTime<Utc>>, usize)> = HashMap::new();
+    for (id, labels) in &images.labels {
+        let count = match labels.keep {
+            None => continue,
+            Some(count) => count,
+        };
+        let created = images.created.get(id);
+        for r in images.refs.get(id).into_iter().flatten() {
+            let newest = counts.entry(&r.repo).or_insert((created, count));
+            if newest.0 < created {
+                *newest = (created, count);
+            }
+        }
+    }
+
+    counts.into_iter().map(|(repo, (_, count))| (repo.to_string(), count)).collect()
+}
+
+#[cfg(test)]
+fn raw(labels: &[(&str, &str)]) -> HashMap<String, String> {
+    labels.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+#[test]
+fn parse_test() {
+    let labels = Labels::parse("id0", &raw(&[(RETAIN, "true"), (KEEP, "10"), (EXPIRES, "2024-12-31"), ("maintainer", "ruimo")]));
+    assert!(labels.retain);
+    assert_eq!(labels.keep, Some(10));
+    assert_eq!(labels.expires, NaiveDate::from_ymd_opt(2024, 12, 31));
+
+    assert_eq!(Labels::parse("id0", &raw(&[(RETAIN, "yes"), (KEEP, "-1"), (EXPIRES, "2024/12/31")])), Labels::default());
+    assert_eq!(Labels::parse("id0", &raw(&[(RETAIN, "false")])), Labels::default());
+}
+
+#[test]
+fn is_expired_test() {
+    let labels = Labels::parse("id0", &raw(&[(EXPIRES, "2024-12-31")]));
+    assert!(!labels.is_expired(NaiveDate::from_ymd_opt(2024, 12, 31).unwrap()));
+    assert!(labels.is_expired(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap()));
+    assert!(!Labels::default().is_expired(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap()));
+}
+
+#[test]
+fn repository_keep_counts_test() {
+    let mut images = image::Images::default();
+    for (id, created, keep) in &[("id0", "2020-10-01T00:00:00Z", "3"), ("id1", "2020-10-02T00:00:00Z", "5"), ("id2", "2020-10-03T00:00:00Z", "")] {
+        images.refs.insert(id.to_string(), vec![image::ImageRef {repo: "repo0".to_string(), tag: id.to_string()}]);
+        images.created.insert(id.to_string(), created.parse().unwrap());
+        let raw_labels = if keep.is_empty() { HashMap::new() } else { raw(&[(KEEP, keep)]) };
+        images.labels.insert(id.to_string(), Labels::parse(id, &raw_labels));
+    }
+
+    let counts = repository_keep_counts(&images);
+    assert_eq!(counts.len(), 1);
+    assert_eq!(counts["repo0"], 5);
+}
@@ -3,8 +3,156 @@ mod image;
 mod version;
 mod docker_registry_type;
 mod image_registry;
+mod plan;
+mod space;
+mod label;
+mod pattern;
+mod git;
+mod promote;
 
-const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use chrono::Utc;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Failures are reported and the remaining images are still removed. Returns false if any removal fails.
+fn remove(registry: &dyn image_registry::ImageRegistry, img: &plan::PlannedImage, args: &arg::Args) -> bool {
+    let results = if img.is_dangling || (args.remove_by_id && img.frees_storage()) {
+        vec![registry.remove_image(&img.id, &img.deleted, args.is_dry_run)]
+    } else {
+        img.deleted.iter().map(|r| registry.remove(&r.repo, &r.tag, args.is_dry_run)).collect()
+    };
+
+    let mut is_success = true;
+    for err in results.into_iter().filter_map(|r| r.err()) {
+        println!("{}", err);
+        is_success = false;
+    }
+    is_success
+}
+
+// Delete images oldest first relaxing keep counts step by step until the target is reached.
+// In dry run, the disk usage is simulated by the estimated reclaimable space. Returns successfully removed tags.
+#[allow(clippy::too_many_arguments)]
+fn delete_until(
+    target: &space::Target, mut usage: space::DiskUsage, registry: &dyn image_registry::ImageRegistry, images: &image::Images,
+    infos: &HashMap<String, space::ImageInfo>, policy: &image::KeepPolicy, protection: &plan::Protection,
+    args: &arg::Args
+) -> HashSet<image::ImageRef> {
+    // Images freeing storage whose size is unknown. They are simulated to free nothing in dry run.
+    let mut unknown_sizes = 0;
+    // (image id, tag)
+    let mut removed: HashSet<(String, image::ImageRef)> = HashSet::new();
+    let mut succeeded: HashSet<image::ImageRef> = HashSet::new();
+    let dangling_before = args.remove_dangling.map(|age| Utc::now() - age);
+    let mut prev: Option<image::KeepPolicy> = None;
+
+    'steps: for step in 0.. {
+        let relaxed = policy.relaxed(step, args.keep_floor);
+        if prev.as_ref() == Some(&relaxed) {
+            break;
+        }
+
+        let mut plan = plan::plan(images, &relaxed, &args.delete_ranges, protection, dangling_before);
+        if step == 0 {
+            for p in &plan.protected {
+                println!("{}", p);
+            }
+        }
+        plan.sort_by_created(&images.created);
+        plan.order_by_dependency(infos);
+        plan.set_reclaimable(infos);
+        if step == 0 {
+            for s in &plan.skipped {
+                println!("{}", s);
+            }
+        }
+
+        for mut img in plan.images {
+            if target.is_reached(&usage) {
+                break 'steps;
+            }
+
+            let id = img.id.clone();
+            img.deleted.retain(|r| !removed.contains(&(id.clone(), r.clone())));
+            if img.deleted.is_empty() {
+                continue;
+            }
+
+            println!("{}", img);
+            let is_success = remove(registry, &img, args);
+            removed.extend(img.deleted.iter().map(|r| (img.id.clone(), r.clone())));
+
+            if !is_success {
+                continue;
+            }
+            succeeded.extend(img.deleted.iter().cloned());
+            if args.is_dry_run {
+                if img.frees_storage() && img.reclaimable.is_none() {
+                    unknown_sizes += 1;
+                }
+                let freed = if img.frees_storage() { img.reclaimable.unwrap_or(0) } else { 0 };
+                usage.used = usage.used.saturating_sub(freed);
+                usage.available += freed;
+            } else if let Some(u) = registry.disk_usage() {
+                usage = u;
+            }
+        }
+
+        prev = Some(relaxed);
+    }
+
+    if target.is_reached(&usage) {
+        println!("Target reached. Available space: {}", space::human_size(usage.available));
+    } else {
+        println!("Target is not reached with keep count floor {}. Available space: {}", args.keep_floor, space::human_size(usage.available));
+    }
+    if unknown_sizes > 0 {
+        println!("Sizes of {} images are unknown and counted as 0. The simulation is incomplete.", unknown_sizes);
+    }
+    succeeded
+}
+
+// Delete images by the keep policy. Returns successfully removed tags.
+fn delete_all(
+    registry: &dyn image_registry::ImageRegistry, images: &image::Images, infos: &HashMap<String, space::ImageInfo>,
+    policy: &image::KeepPolicy, protection: &plan::Protection, args: &arg::Args
+) -> HashSet<image::ImageRef> {
+    let dangling_before = args.remove_dangling.map(|age| Utc::now() - age);
+    let mut plan = plan::plan(images, policy, &args.delete_ranges, protection, dangling_before);
+    plan.order_by_dependency(infos);
+    plan.set_reclaimable(infos);
+    for p in &plan.protected {
+        println!("{}", p);
+    }
+    for s in &plan.skipped {
+        println!("{}", s);
+    }
+    for img in &plan.images {
+        println!("{}", img);
+    }
+    if let Some(bytes) = plan.total_reclaimable() {
+        println!("Estimated reclaimable space: {}", space::human_size(bytes));
+    }
+
+    let before = if args.is_dry_run { None } else { registry.disk_usage() };
+
+    let mut succeeded: HashSet<image::ImageRef> = HashSet::new();
+    for img in &plan.images {
+        if remove(registry, img, args) {
+            succeeded.extend(img.deleted.iter().cloned());
+        }
+    }
+
+    if let Some(before) = before {
+        if let Some(after) = registry.disk_usage() {
+            println!("Reclaimed space: {}", space::human_size(after.available.saturating_sub(before.available)));
+        }
+    }
+    succeeded
+}
 
 fn main() {
     let args: arg::Args = arg::parse_arg();
@@ -12,14 +160,82 @@ fn main() {
     if args.show_version {
         println!("{}", VERSION);
     } else {
+        let docker = image_registry::DockerImageRegistry::new(args.docker_host.as_deref(), args.docker_context.as_deref());
         let registry: &dyn image_registry::ImageRegistry = match args.repository_type {
-            docker_registry_type::DockerRegistryType::Docker => &image_registry::DOCKER_IMAGE_REGISTRY,
+            docker_registry_type::DockerRegistryType::Docker => &docker,
             docker_registry_type::DockerRegistryType::IbmCloudRegistry => &image_registry::IBM_CLOUD_REGISTRY,
         };
 
-        let images = registry.list();
-        images.delete(args.keep_count, args.keep_count_snapshot, |repo, ver| {
-            registry.remove(repo, ver, args.is_dry_run);
-        });
+        let ver_parser = match version::parser_with_markers(&args.snapshot_markers) {
+            Result::Ok(parser) => parser,
+            Result::Err(err) => panic!("{}", err),
+        }.with_variants(&args.variants).with_components(args.is_component_enabled);
+        let policy = image::KeepPolicy {
+            canonical: args.keep_count,
+            snapshot: args.keep_count_snapshot,
+            describe: args.keep_count_describe,
+            markers: args.snapshot_markers.iter()
+                .filter_map(|m| m.keep_count.map(|count| (m.text.clone(), count)))
+                .collect(),
+            repositories: HashMap::new(),
+            lines: image::LinePolicy { per_line: args.keep_per_line, lines: args.keep_lines },
+            buckets: args.snapshot_buckets.map(|(daily, weekly, monthly)| image::BucketPolicy {
+                daily, weekly, monthly, today: Utc::now().date_naive()
+            }),
+            stale_branches: HashSet::new(),
+            release_groups: args.release_groups.clone(),
+            branch_active_since: args.branch_ttl.map(|ttl| Utc::now() - ttl),
+            max_branches: args.max_branches,
+            feature_branch: args.feature_branch.clone(),
+        };
+
+        // Checked before anything is removed.
+        let usage = match &args.target {
+            None => None,
+            Some(target) => match registry.disk_usage() {
+                Some(usage) => Some((target, usage)),
+                None => {
+                    println!("Cannot obtain disk usage of docker root directory. Target free space/usage is not available.");
+                    std::process::exit(1);
+                }
+            },
+        };
+
+        let mut images = registry.list(&ver_parser, args.commit_tag_pattern.as_ref());
+        let ids: Vec<&str> = images.refs.keys().map(|id| id.as_str()).collect();
+        let infos = registry.inspect(&ids);
+        images.labels = label::from_infos(&infos);
+        let mut policy = image::KeepPolicy { repositories: label::repository_keep_counts(&images), ..policy };
+        if let Some(repo) = &args.git_repo {
+            let branches = git::list_branches(repo, args.git_merged_into.as_deref());
+            let image_branches: HashSet<&str> = images.entries.values().flatten()
+                .filter_map(|e| e.ver.branch.as_deref())
+                .collect();
+            let mut stale: Vec<(String, String)> = git::stale_branches(image_branches.into_iter(), &branches, &args.branch_maps)
+                .into_iter().collect();
+            stale.sort();
+            for (branch, reason) in stale {
+                println!("Branch {} is stale ({})", branch, reason);
+                policy.stale_branches.insert(branch);
+            }
+        }
+
+        let protection = plan::Protection {
+            floating_tags: args.protected_tags.clone(), today: Utc::now().date_naive(), pins: args.pins.clone(),
+        };
+
+        let removed = match usage {
+            Some((target, usage)) => delete_until(target, usage, registry, &images, &infos, &policy, &protection, &args),
+            None => delete_all(registry, &images, &infos, &policy, &protection, &args),
+        };
+
+        if let Some(tags) = &args.promote {
+            for p in promote::promotions(&images, &removed, tags) {
+                println!("{}", p);
+                if let Err(err) = registry.tag(&p.source, &p.target, args.is_dry_run) {
+                    println!("{}", err);
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,224 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use super::version;
+
+#[cfg(test)]
+use super::version::parser;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+// Space separated constraints such as '>=2.3 <2.4'. All of them must be satisfied.
+// Only version numbers are compared. Missing components are zero, so '1.2' equals '1.2.0'.
+// A component tag such as 'api-1.2.3' is matched only by the range of the component such as 'api-<2.0'.
+#[derive(PartialEq, Debug, Clone)]
+pub struct VersionRange {
+    component: Option<String>,
+    constraints: Vec<(Op, Vec<u64>)>,
+}
+
+fn cmp_numbers(a: &[u64], b: &[u64]) -> Ordering {
+    let len = a.len().max(b.len());
+    for i in 0..len {
+        let c = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if c != Ordering::Equal {
+            return c;
+        }
+    }
+    Ordering::Equal
+}
+
+fn parse_numbers(s: &str) -> Option<Vec<u64>> {
+    s.trim_start_matches(['v', 'V']).split('.').map(|n| n.parse().ok()).collect()
+}
+
+// Split 'api-<2.0' into ('api', '<2.0').
+fn split_component(s: &str) -> (Option<&str>, &str) {
+    match s.find(['<', '>', '=']) {
+        Some(idx) if idx > 1 && s.starts_with(|c: char| c.is_ascii_alphabetic()) && s[..idx].ends_with('-') =>
+            (Some(&s[..idx - 1]), &s[idx..]),
+        _ => (None, s),
+    }
+}
+
+pub fn is_range(s: &str) -> bool {
+    split_component(s).1.starts_with(['<', '>', '='])
+}
+
+impl VersionRange {
+    pub fn parse(s: &str) -> Result<VersionRange, String> {
+        let (component, range) = split_component(s);
+        let mut constraints = Vec::new();
+        for token in range.split(|c: char| c.is_whitespace() || c == ',').filter(|t| !t.is_empty()) {
+            let (op, rest) = if let Some(rest) = token.strip_prefix(">=") {
+                (Op::Ge, rest)
+            } else if let Some(rest) = token.strip_prefix("<=") {
+                (Op::Le, rest)
+            } else if let Some(rest) = token.strip_prefix('>') {
+                (Op::Gt, rest)
+            } else if let Some(rest) = token.strip_prefix('<') {
+                (Op::Lt, rest)
+            } else if let Some(rest) = token.strip_prefix('=') {
+                (Op::Eq, rest)
+            } else {
+                return Result::Err(format!("Invalid version range '{}'. Each constraint should start with <, <=, >, >= or =.", s));
+            };
+
+            match parse_numbers(rest) {
+                Some(numbers) => constraints.push((op, numbers)),
+                None => return Result::Err(format!("Invalid version '{}' in version range '{}'.", rest, s)),
+            }
+        }
+
+        if constraints.is_empty() {
+            Result::Err(format!("Version range '{}' is empty.", s))
+        } else {
+            Result::Ok(VersionRange { component: component.map(|c| c.to_string()), constraints })
+        }
+    }
+
+    pub fn matches(&self, ver: &version::Version) -> bool {
+        ver.component == self.component && self.constraints.iter().all(|(op, numbers)| {
+            let c = cmp_numbers(&ver.numbers, numbers);
+            match op {
+                Op::Lt => c == Ordering::Less,
+                Op::Le => c != Ordering::Greater,
+                Op::Gt => c == Ordering::Greater,
+                Op::Ge => c != Ordering::Less,
+                Op::Eq => c == Ordering::Equal,
+            }
+        })
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Matcher {
+    Tag(String),
+    Range(VersionRange),
+}
+
+// 'repo:tag' or 'repo:range' such as 'myapp:>=2.3 <2.4'. Repository can be omitted to match all repositories.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ImagePattern {
+    repo: Option<String>,
+    matcher: Matcher,
+    raw: String,
+}
+
+impl ImagePattern {
+    pub fn parse(s: &str) -> Result<ImagePattern, String> {
+        let s = s.trim();
+        // Repository may have a registry port such as 'localhost:5000/myapp', so split at the last colon.
+        let (repo, spec) = match s.rsplit_once(':') {
+            Some((repo, spec)) => (Some(repo.to_string()), spec),
+            None => (None, s),
+        };
+
+        let matcher = if is_range(spec) {
+            Matcher::Range(VersionRange::parse(spec)?)
+        } else if spec.is_empty() {
+            return Result::Err(format!("Tag is empty in '{}'.", s));
+        } else {
+            Matcher::Tag(spec.to_string())
+        };
+
+        Result::Ok(ImagePattern { repo, matcher, raw: s.to_string() })
+    }
+
+    // ver is None if the tag is not a version.
+    pub fn matches(&self, repo: &str, tag: &str, ver: Option<&version::Version>) -> bool {
+        if self.repo.as_ref().is_some_and(|r| r != repo) {
+            return false;
+        }
+        match &self.matcher {
+            Matcher::Tag(t) => t == tag,
+            Matcher::Range(range) => ver.is_some_and(|v| range.matches(v)),
+        }
+    }
+}
+
+impl fmt::Display for ImagePattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+// One pattern per line. Empty lines and lines starting with '#' are ignored.
+pub fn parse_keep_file(content: &str) -> Result<Vec<ImagePattern>, String> {
+    content.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(ImagePattern::parse)
+        .collect()
+}
+
+#[test]
+fn version_range_test() {
+    let parser = parser();
+    let range = VersionRange::parse(">=2.3 <2.4").unwrap();
+    assert!(!range.matches(&parser.parse("2.2.9").unwrap()));
+    assert!(range.matches(&parser.parse("2.3").unwrap()));
+    assert!(range.matches(&parser.parse("v2.3.15").unwrap()));
+    assert!(range.matches(&parser.parse("2.3.1-SNAPSHOT").unwrap()));
+    assert!(!range.matches(&parser.parse("2.4.0").unwrap()));
+
+    assert!(VersionRange::parse("<=1.2").unwrap().matches(&parser.parse("1.2.0").unwrap()));
+    assert!(VersionRange::parse("=1.2").unwrap().matches(&parser.parse("1.2.0").unwrap()));
+    assert!(VersionRange::parse(">1.2").unwrap().matches(&parser.parse("1.2.1").unwrap()));
+
+    assert!(VersionRange::parse("").is_err());
+    assert!(VersionRange::parse("2.3").is_err());
+    assert!(VersionRange::parse(">=2.x").is_err());
+}
+
+#[test]
+fn component_range_test() {
+    let parser = parser().with_components(true);
+    let range = VersionRange::parse("api-<2.0").unwrap();
+    assert!(range.matches(&parser.parse("api-1.2.3").unwrap()));
+    assert!(!range.matches(&parser.parse("worker-1.2.3").unwrap()));
+    assert!(!range.matches(&parser.parse("1.2.3").unwrap()));
+    assert!(!VersionRange::parse("<2.0").unwrap().matches(&parser.parse("api-1.2.3").unwrap()));
+
+    let p = ImagePattern::parse("repo:api-<2.0").unwrap();
+    assert!(p.matches("repo", "api-1.2.3", Some(&parser.parse("api-1.2.3").unwrap())));
+    assert!(!p.matches("repo", "web-1.2.3", Some(&parser.parse("web-1.2.3").unwrap())));
+    assert!(is_range("my-api->=2.0"));
+    assert!(!is_range("1.0"));
+}
+
+#[test]
+fn image_pattern_test() {
+    let parser = parser();
+    let v = parser.parse("2.3.1").unwrap();
+
+    let p = ImagePattern::parse("myapp:>=2.3 <2.4").unwrap();
+    assert!(p.matches("myapp", "2.3.1", Some(&v)));
+    assert!(!p.matches("other", "2.3.1", Some(&v)));
+    assert!(!p.matches("myapp", "latest", None));
+
+    let p = ImagePattern::parse("localhost:5000/myapp:2.3.1").unwrap();
+    assert!(p.matches("localhost:5000/myapp", "2.3.1", Some(&v)));
+    assert!(!p.matches("localhost:5000/myapp", "2.3.10", None));
+
+    let p = ImagePattern::parse("<2.4").unwrap();
+    assert!(p.matches("any", "2.3.1", Some(&v)));
+    assert_eq!(p.to_string(), "<2.4");
+
+    assert!(ImagePattern::parse("myapp:").is_err());
+}
+
+#[test]
+fn parse_keep_file_test() {
+    let patterns = parse_keep_file("# LTS\nmyapp:>=2.3 <2.4\n\n  myapp:1.0.5  \n").unwrap();
+    assert_eq!(patterns.len(), 2);
+    assert_eq!(patterns[1].to_string(), "myapp:1.0.5");
+    assert!(parse_keep_file("myapp:>=2.x").is_err());
+}
@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::image;
+use super::label;
+use super::pattern;
+use super::space;
+
+#[cfg(test)]
+use super::version;
+
+pub struct PlannedImage {
+    pub id: String,
+    pub deleted: Vec<image::ImageRef>,
+    // Other tags of the same image that are kept.
+    pub kept: Vec<image::ImageRef>,
+    // Estimated bytes freed by the deletion. None if unknown.
+    pub reclaimable: Option<u64>,
+    // Untagged image. It is removed by id.
+    pub is_dangling: bool,
+}
+
+impl PlannedImage {
+    // Removing tags frees storage only if no tag remains on the image.
+    pub fn frees_storage(&self) -> bool {
+        self.kept.is_empty()
+    }
+}
+
+impl fmt::Display for PlannedImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let deleted: Vec<String> = self.deleted.iter().map(|r| r.to_string()).collect();
+        if self.frees_storage() {
+            match self.reclaimable {
+                Some(bytes) => write!(f, "Image {}: delete {} (frees {})", self.id, deleted.join(", "), space::human_size(bytes)),
+                None => write!(f, "Image {}: delete {} (frees storage)", self.id, deleted.join(", ")),
+            }
+        } else {
+            let kept: Vec<String> = self.kept.iter().map(|r| r.to_string()).collect();
+            write!(f, "Image {}: untag {} (storage is kept by {})", self.id, deleted.join(", "), kept.join(", "))
+        }
+    }
+}
+
+pub const DEFAULT_PROTECTED_TAGS: [&str; 3] = ["latest", "stable", "prod"];
+
+// Tags that would be deleted by the keep policy but must be kept.
+pub struct Protection {
+    // Images having one of these tags in any repository are kept.
+    pub floating_tags: Vec<String>,
+    // Used to tell whether io.ruimo.expires label is passed.
+    pub today: NaiveDate,
+    // Images having a tag matching one of these patterns are kept. Read from --keep-file.
+    pub pins: Vec<pattern::ImagePattern>,
+}
+
+impl Protection {
+    // Returns the reason if the tag must be kept.
+    pub fn reason(&self, images: &image::Images, id: &str) -> Option<String> {
+        if let Some(labels) = images.labels.get(id) {
+            if labels.retain {
+                return Some(format!("image has label {}=true", label::RETAIN));
+            }
+            if let Some(date) = labels.expires.filter(|_| !labels.is_expired(self.today)) {
+                return Some(format!("image has label {}={}", label::EXPIRES, date));
+            }
+        }
+        let refs = images.refs.get(id)?;
+        for r in refs {
+            if let Some(pin) = self.pins.iter().find(|p| p.matches(&r.repo, &r.tag, images.version_of(&r.repo, &r.tag))) {
+                return Some(format!("{} is pinned by '{}' in keep file", r, pin));
+            }
+        }
+        let floating = refs.iter().find(|other| self.floating_tags.contains(&other.tag))?;
+        Some(format!("image is tagged by {}", floating))
+    }
+
+    // Image of which io.ruimo.expires label is passed. It is deleted regardless of the keep count.
+    pub fn is_expired(&self, images: &image::Images, id: &str) -> bool {
+        images.labels.get(id).is_some_and(|labels| labels.is_expired(self.today))
+    }
+}
+
+pub struct ProtectedRef {
+    pub image_ref: image::ImageRef,
+    pub reason: String,
+}
+
+impl fmt::Display for ProtectedRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Keep {} ({})", self.image_ref, self.reason)
+    }
+}
+
+// Image that cannot be removed since kept child images depend on it.
+pub struct SkippedImage {
+    pub image: PlannedImage,
+    pub children: Vec<String>,
+}
+
+impl fmt::Display for SkippedImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let deleted: Vec<String> = self.image.deleted.iter().map(|r| r.to_string()).collect();
+        write!(f, "Skip image {}: {} (kept child images {} depend on it)", self.image.id, deleted.join(", "), self.children.join(", "))
+    }
+}
+
+pub struct Plan {
+    // Sorted by image id.
+    pub images: Vec<PlannedImage>,
+    // Sorted by tag.
+    pub protected: Vec<ProtectedRef>,
+    pub skipped: Vec<SkippedImage>,
+}
+
+// If ranges are specified, tags matching them are deleted instead of applying the keep policy.
+// Dangling images created before dangling_before are also deleted.
+pub fn plan(
+    images: &image::Images, policy: &image::KeepPolicy, ranges: &[pattern::ImagePattern], protection: &Protection,
+    dangling_before: Option<DateTime<Utc>>
+) -> Plan {
+    // key: image id
+    let mut deleted: HashMap<String, Vec<image::ImageRef>> = HashMap::new();
+    let mut protected: Vec<ProtectedRef> = Vec::new();
+    let mut del = |repo: &str, tag: &str, id: &str| {
+        let r = image::ImageRef {repo: repo.to_string(), tag: tag.to_string()};
+        match protection.reason(images, id) {
+            Some(reason) => protected.push(ProtectedRef {image_ref: r, reason}),
+            None => deleted.entry(id.to_string()).or_default().push(r),
+        }
+    };
+    if ranges.is_empty() {
+        images.delete(policy, &mut del);
+    } else {
+        images.each_tag(|repo, tag, id, ver| {
+            if ranges.iter().any(|range| range.matches(repo, tag, ver)) {
+                del(repo, tag, id);
+            }
+        });
+    }
+    images.each_tag(|repo, tag, id, _ver| {
+        let r = image::ImageRef {repo: repo.to_string(), tag: tag.to_string()};
+        if protection.is_expired(images, id) && protection.reason(images, id).is_none() {
+            let refs = deleted.entry(id.to_string()).or_default();
+            if !refs.contains(&r) {
+                refs.push(r);
+            }
+        }
+    });
+    protected.sort_by_key(|p| p.image_ref.to_string());
+
+    let mut planned: Vec<PlannedImage> = deleted.into_iter().map(|(id, mut deleted)| {
+        let mut kept: Vec<image::ImageRef> = images.refs.get(&id).map(|refs| {
+            refs.iter()
+                .filter(|r| !deleted.contains(r))
+                .cloned()
+                .collect()
+        }).unwrap_or_default();
+
+        deleted.sort_by_key(|r| r.to_string());
+        kept.sort_by_key(|r| r.to_string());
+        PlannedImage {id, deleted, kept, reclaimable: None, is_dangling: false}
+    }).collect();
+
+    if let Some(before) = dangling_before {
+        for (id, r) in &images.dangling {
+            if images.created.get(id).is_some_and(|created| *created < before) {
+                planned.push(PlannedImage {
+                    id: id.clone(), deleted: vec![r.clone()], kept: Vec::new(), reclaimable: None, is_dangling: true
+                });
+            }
+        }
+    }
+    planned.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Plan {images: planned, protected, skipped: Vec::new()}
+}
+
+impl Plan {
+    pub fn set_reclaimable(&mut self, infos: &HashMap<String, space::ImageInfo>) {
+        let deleted = self.images.iter()
+            .filter(|img| img.frees_storage())
+            .map(|img| img.id.clone())
+            .collect();
+        let freed = space::reclaimable(infos, &deleted);
+
+        for img in &mut self.images {
+            img.reclaimable = freed.get(&img.id).cloned();
+        }
+    }
+
+    // Oldest image first. Images without creation time come last.
+    pub fn sort_by_created(&mut self, created: &HashMap<String, DateTime<Utc>>) {
+        self.images.sort_by_key(|img| (created.get(&img.id).is_none(), created.get(&img.id).cloned()));
+    }
+
+    // Skip images that kept child images depend on, and remove child images before their parents.
+    // Otherwise, the order is preserved.
+    pub fn order_by_dependency(&mut self, infos: &HashMap<String, space::ImageInfo>) {
+        // key: full id, value: image id
+        let by_full_id: HashMap<&str, &str> = infos.iter().map(|(id, info)| (info.full_id.as_str(), id.as_str())).collect();
+        // key: parent image id
+        let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (id, info) in infos {
+            if let Some(parent) = info.parent.as_ref().and_then(|p| by_full_id.get(p.as_str())) {
+                children.entry(parent).or_default().push(id);
+            }
+        }
+
+        loop {
+            let blocked = {
+                let removed: HashSet<&str> = self.images.iter()
+                    .filter(|img| img.frees_storage())
+                    .map(|img| img.id.as_str())
+                    .collect();
+                self.images.iter().enumerate().find_map(|(i, img)| {
+                    if !img.frees_storage() {
+                        return None;
+                    }
+                    let mut kept: Vec<String> = children.get(img.id.as_str())?.iter()
+                        .filter(|c| !removed.contains(*c))
+                        .map(|c| c.to_string())
+                        .collect();
+                    kept.sort();
+                    if kept.is_empty() { None } else { Some((i, kept)) }
+                })
+            };
+
+            match blocked {
+                None => break,
+                Some((idx, kept)) => {
+                    let image = self.images.remove(idx);
+                    self.skipped.push(SkippedImage {image, children: kept});
+                }
+            }
+        }
+
+        let mut ordered: Vec<PlannedImage> = Vec::new();
+        let mut rest: Vec<Option<PlannedImage>> = self.images.drain(..).map(Some).collect();
+        let index: HashMap<String, usize> = rest.iter().enumerate()
+            .map(|(i, img)| (img.as_ref().unwrap().id.clone(), i))
+            .collect();
+
+        fn push_with_children(
+            i: usize, rest: &mut Vec<Option<PlannedImage>>, ordered: &mut Vec<PlannedImage>,
+            index: &HashMap<String, usize>, children: &HashMap<&str, Vec<&str>>
+        ) {
+            if let Some(img) = rest[i].take() {
+                if let Some(cs) = children.get(img.id.as_str()) {
+                    for c in cs {
+                        if let Some(ci) = index.get(*c) {
+                            push_with_children(*ci, rest, ordered, index, children);
+                        }
+                    }
+                }
+                ordered.push(img);
+            }
+        }
+
+        for i in 0..rest.len() {
+            push_with_children(i, &mut rest, &mut ordered, &index, &children);
+        }
+        self.images = ordered;
+    }
+
+    // None if the size of any storage freeing image is unknown.
+    pub fn total_reclaimable(&self) -> Option<u64> {
+        self.images.iter()
+            .filter(|img| img.frees_storage())
+            .map(|img| img.reclaimable)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+fn image_ref(repo: &str, tag: &str) -> image::ImageRef {
+    image::ImageRef {repo: repo.to_string(), tag: tag.to_string()}
+}
+
+#[cfg(test)]
+fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2020, 10, 18).unwrap()
+}
+
+#[cfg(test)]
+fn test_images() -> image::Images {
+    let parser = version::parser();
+    let entries0 = image::entries(&parser, &[("id00", "1.3"), ("id00", "1.3.0"), ("id01", "1.4"), ("id02", "1.5")]);
+
+    let mut entries = HashMap::new();
+    entries.insert("repo0".to_string(), entries0);
+
+    let mut refs = HashMap::new();
+    refs.insert("id00".to_string(), vec![image_ref("repo0", "1.3"), image_ref("repo0", "1.3.0")]);
+    refs.insert("id01".to_string(), vec![image_ref("repo0", "1.4"), image_ref("repo0", "stable")]);
+    refs.insert("id02".to_string(), vec![image_ref("repo0", "1.5")]);
+
+    image::Images { entries, refs, ..Default::default() }
+}
+
+#[test]
+fn plan_test() {
+    let images = test_images();
+    let policy = image::KeepPolicy { canonical: 1, snapshot: 1, ..Default::default() };
+
+    let p = plan(&images, &policy, &[], &Protection {floating_tags: Vec::new(), today: today(), pins: Vec::new()}, None);
+    assert_eq!(p.images.len(), 2);
+
+    assert_eq!(p.images[0].id, "id00");
+    assert!(p.images[0].frees_storage());
+    assert_eq!(p.images[0].to_string(), "Image id00: delete repo0:1.3, repo0:1.3.0 (frees storage)");
+
+    assert_eq!(p.images[1].id, "id01");
+    assert!(!p.images[1].frees_storage());
+    assert_eq!(p.images[1].to_string(), "Image id01: untag repo0:1.4 (storage is kept by repo0:stable)");
+}
+
+#[test]
+fn reclaimable_test() {
+    let mut p = Plan {images: vec![
+        PlannedImage {id: "id00".to_string(), deleted: vec![image_ref("repo0", "1.0")], kept: Vec::new(), reclaimable: None, is_dangling: false},
+        PlannedImage {id: "id01".to_string(), deleted: vec![image_ref("repo0", "1.1")], kept: vec![image_ref("repo0", "stable")], reclaimable: None, is_dangling: false},
+    ], protected: Vec::new(), skipped: Vec::new()};
+    assert_eq!(p.total_reclaimable(), None);
+
+    let mut infos = HashMap::new();
+    infos.insert("id00".to_string(), space::ImageInfo {full_id: "sha256:id00".to_string(), parent: None, size: 1500, layers: vec!["a".to_string(), "b".to_string()], labels: HashMap::new()});
+    infos.insert("id01".to_string(), space::ImageInfo {full_id: "sha256:id01".to_string(), parent: None, size: 1000, layers: vec!["a".to_string()], labels: HashMap::new()});
+    p.set_reclaimable(&infos);
+
+    assert_eq!(p.images[0].reclaimable, Some(500));
+    assert_eq!(p.images[1].reclaimable, None);
+    assert_eq!(p.total_reclaimable(), Some(500));
+    assert_eq!(p.images[0].to_string(), "Image id00: delete repo0:1.0 (frees 500 B)");
+}
+
+#[test]
+fn sort_by_created_test() {
+    let planned = |id: &str| PlannedImage {id: id.to_string(), deleted: vec![image_ref("repo0", id)], kept: Vec::new(), reclaimable: None, is_dangling: false};
+    let mut p = Plan {images: vec![planned("id00"), planned("id01"), planned("id02")], protected: Vec::new(), skipped: Vec::new()};
+
+    let mut created = HashMap::new();
+    created.insert("id00".to_string(), "2020-10-03T00:00:00Z".parse().unwrap());
+    created.insert("id02".to_string(), "2020-10-01T00:00:00Z".parse().unwrap());
+    p.sort_by_created(&created);
+
+    let ids: Vec<&str> = p.images.iter().map(|img| img.id.as_str()).collect();
+    assert_eq!(ids, vec!["id02", "id00", "id01"]);
+}
+
+#[test]
+fn protected_test() {
+    let images = test_images();
+    let policy = image::KeepPolicy { canonical: 1, snapshot: 1, ..Default::default() };
+    let protection = Protection {floating_tags: vec!["stable".to_string()], today: today(), pins: Vec::new()};
+
+    let p = plan(&images, &policy, &[], &protection, None);
+    assert_eq!(p.images.len(), 1);
+    assert_eq!(p.images[0].id, "id00");
+    assert_eq!(p.protected.len(), 1);
+    assert_eq!(p.protected[0].to_string(), "Keep repo0:1.4 (image is tagged by repo0:stable)");
+}
+
+#[test]
+fn order_by_dependency_test() {
+    let planned = |id: &str| PlannedImage {id: id.to_string(), deleted: vec![image_ref("repo0", id)], kept: Vec::new(), reclaimable: None, is_dangling: false};
+    let info = |id: &str, parent: Option<&str>| space::ImageInfo {
+        full_id: format!("sha256:{}", id), parent: parent.map(|p| format!("sha256:{}", p)), size: 0, layers: Vec::new(), labels: HashMap::new()
+    };
+    let mut infos = HashMap::new();
+    infos.insert("base".to_string(), info("base", None));
+    infos.insert("app0".to_string(), info("app0", Some("base")));
+    infos.insert("base1".to_string(), info("base1", None));
+    infos.insert("app1".to_string(), info("app1", Some("base1")));
+    infos.insert("app2".to_string(), info("app2", Some("app1")));
+
+    // app1 is kept, so base1 cannot be removed.
+    let mut p = Plan {images: vec![planned("base"), planned("base1"), planned("app0"), planned("app2")], protected: Vec::new(), skipped: Vec::new()};
+    p.order_by_dependency(&infos);
+
+    let ids: Vec<&str> = p.images.iter().map(|img| img.id.as_str()).collect();
+    assert_eq!(ids, vec!["app0", "base", "app2"]);
+    assert_eq!(p.skipped.len(), 1);
+    assert_eq!(p.skipped[0].to_string(), "Skip image base1: repo0:base1 (kept child images app1 depend on it)");
+}
+
+#[test]
+fn dangling_test() {
+    let mut images = test_images();
+    for (id, created) in &[("id10", "2020-10-01T00:00:00Z"), ("id11", "2020-10-03T00:00:00Z")] {
+        images.dangling.insert(id.to_string(), image_ref("<none>", "<none>"));
+        images.refs.insert(id.to_string(), Vec::new());
+        images.created.insert(id.to_string(), created.parse().unwrap());
+    }
+    let policy = image::KeepPolicy { canonical: 4, snapshot: 1, ..Default::default() };
+    let protection = Protection {floating_tags: Vec::new(), today: today(), pins: Vec::new()};
+
+    let p = plan(&images, &policy, &[], &protection, None);
+    assert!(p.images.is_empty());
+
+    let p = plan(&images, &policy, &[], &protection, Some("2020-10-02T00:00:00Z".parse().unwrap()));
+    assert_eq!(p.images.len(), 1);
+    assert!(p.images[0].is_dangling);
+    assert!(p.images[0].frees_storage());
+    assert_eq!(p.images[0].to_string(), "Image id10: delete <none>:<none> (frees storage)");
+}
+
+#[test]
+fn label_test() {
+    let mut images = test_images();
+    let policy = image::KeepPolicy { canonical: 2, snapshot: 1, ..Default::default() };
+    let protection = Protection {floating_tags: Vec::new(), today: today(), pins: Vec::new()};
+    let labels = |retain: bool, expires: Option<(i32, u32, u32)>| label::Labels {
+        retain, keep: None, expires: expires.and_then(|(y, m, d)| NaiveDate::from_ymd_opt(y, m, d))
+    };
+
+    images.labels.insert("id00".to_string(), labels(true, None));
+    let p = plan(&images, &policy, &[], &protection, None);
+    assert!(p.images.is_empty());
+    assert_eq!(p.protected.len(), 2);
+    assert_eq!(p.protected[0].to_string(), "Keep repo0:1.3 (image has label io.ruimo.retain=true)");
+
+    images.labels.insert("id00".to_string(), labels(false, Some((2020, 10, 18))));
+    let p = plan(&images, &policy, &[], &protection, None);
+    assert!(p.images.is_empty());
+    assert_eq!(p.protected[0].to_string(), "Keep repo0:1.3 (image has label io.ruimo.expires=2020-10-18)");
+
+    // Expired image is deleted even if the keep count keeps it.
+    images.labels.insert("id00".to_string(), labels(false, Some((2020, 10, 17))));
+    images.labels.insert("id02".to_string(), labels(false, Some((2020, 10, 17))));
+    let p = plan(&images, &policy, &[], &protection, None);
+    let ids: Vec<&str> = p.images.iter().map(|img| img.id.as_str()).collect();
+    assert_eq!(ids, vec!["id00", "id02"]);
+    assert_eq!(p.images[0].to_string(), "Image id00: delete repo0:1.3, repo0:1.3.0 (frees storage)");
+}
+
+#[test]
+fn pin_test() {
+    let images = test_images();
+    let policy = image::KeepPolicy { canonical: 1, snapshot: 1, ..Default::default() };
+    let pins = pattern::parse_keep_file("repo0:>=1.3 <1.4").unwrap();
+    let protection = Protection {floating_tags: Vec::new(), today: today(), pins};
+
+    let p = plan(&images, &policy, &[], &protection, None);
+    assert_eq!(p.images.len(), 1);
+    assert_eq!(p.images[0].id, "id01");
+    assert_eq!(p.protected.len(), 2);
+    assert_eq!(p.protected[0].to_string(), "Keep repo0:1.3 (repo0:1.3 is pinned by 'repo0:>=1.3 <1.4' in keep file)");
+}
+
+#[test]
+fn range_test() {
+    let images = test_images();
+    let policy = image::KeepPolicy::default();
+    let protection = Protection {floating_tags: vec!["stable".to_string()], today: today(), pins: Vec::new()};
+
+    // Keep count is not applied, so 1.5 is deleted by the range only.
+    let p = plan(&images, &policy, &[pattern::ImagePattern::parse("<=1.5").unwrap()], &protection, None);
+    let ids: Vec<&str> = p.images.iter().map(|img| img.id.as_str()).collect();
+    assert_eq!(ids, vec!["id00", "id02"]);
+    assert_eq!(p.protected.len(), 1);
+
+    let p = plan(&images, &policy, &[pattern::ImagePattern::parse("repo0:<1.5").unwrap()], &protection, None);
+    assert_eq!(p.images.len(), 1);
+    assert_eq!(p.images[0].to_string(), "Image id00: delete repo0:1.3, repo0:1.3.0 (frees storage)");
+
+    let p = plan(&images, &policy, &[pattern::ImagePattern::parse("repo1:<1.5").unwrap()], &protection, None);
+    assert!(p.images.is_empty());
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+
+use super::image;
+use super::version;
+
+// Floating tags pointed at the newest kept version. None disables the kind of tag.
+pub struct PromoteTags {
+    // Canonical versions without a branch such as 'latest'.
+    pub canonical: Option<String>,
+    // Snapshot versions without a branch such as 'latest-snapshot'.
+    pub snapshot: Option<String>,
+    // Versions of each branch such as '{branch}-latest'. Canonical versions are preferred to snapshots.
+    pub branch: Option<String>,
+}
+
+impl fmt::Debug for PromoteTags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PromoteTags(canonical: {:?}, snapshot: {:?}, branch: {:?})", self.canonical, self.snapshot, self.branch)
+    }
+}
+
+pub struct Promotion {
+    pub source: image::ImageRef,
+    pub target: image::ImageRef,
+}
+
+impl fmt::Display for Promotion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Promote {} to {}", self.source, self.target)
+    }
+}
+
+// Floating tag for the version. Prefixed by the component if any. None if disabled.
+fn floating_tag(tags: &PromoteTags, ver: &version::Version) -> Option<String> {
+    let tag = match (&ver.branch, ver.is_snapshot) {
+        (None, false) => tags.canonical.clone()?,
+        (None, true) => tags.snapshot.clone()?,
+        (Some(branch), _) => tags.branch.as_ref()?.replace("{branch}", branch),
+    };
+    match &ver.component {
+        Some(component) => Some(format!("{}-{}", component, tag)),
+        None => Some(tag),
+    }
+}
+
+// Canonical versions are newer than snapshots in a branch. Snapshot markers are not compared, since
+// '1.5-SNAPSHOT' is newer than '1.2-dev' although both get the same floating tag.
+fn rank(e: &image::ImageEntry) -> (bool, &Vec<u64>, &Option<version::SnapshotStamp>) {
+    (!e.ver.is_snapshot, &e.ver.numbers, &e.ver.stamp)
+}
+
+// Floating tags to be pointed at the newest version that is not removed. The tag without a variant
+// is the source if any, since it is the version of the entry. Git describe versions are not promoted.
+// Floating tags already pointing at the image are skipped. Sorted by the floating tag.
+pub fn promotions(images: &image::Images, removed: &HashSet<image::ImageRef>, tags: &PromoteTags) -> Vec<Promotion> {
+    let mut promotions = Vec::new();
+
+    for (repo, entry) in &images.entries {
+        // key: floating tag
+        let mut newest: HashMap<String, &image::ImageEntry> = HashMap::new();
+        for e in entry {
+            if e.ver.describe.is_some() || removed.contains(&image::ImageRef {repo: repo.clone(), tag: e.ver.raw.clone()}) {
+                continue;
+            }
+            let tag = match floating_tag(tags, &e.ver) {
+                Some(tag) => tag,
+                None => continue,
+            };
+            let n = newest.entry(tag).or_insert(e);
+            if rank(n) < rank(e) {
+                *n = e;
+            }
+        }
+
+        for (tag, e) in newest {
+            let target = image::ImageRef {repo: repo.clone(), tag};
+            if images.refs.get(&e.id).is_some_and(|refs| refs.contains(&target)) {
+                continue;
+            }
+            promotions.push(Promotion {source: image::ImageRef {repo: repo.clone(), tag: e.ver.raw.clone()}, target});
+        }
+    }
+
+    promotions.sort_by_key(|p| p.target.to_string());
+    promotions
+}
+
+#[test]
+fn promotions_test() {
+    let parser = version::parser();
+    let vers = ["1.2", "1.3", "1.4-SNAPSHOT", "1.3-BR102-SNAPSHOT", "1.2-BR103", "1.3-BR103-SNAPSHOT", "1.3-2-gabc1234"];
+    let entries = image::entries(&parser, &vers.map(|v| (v, v)));
+    let mut refs = HashMap::new();
+    for ver in &vers {
+        refs.insert(ver.to_string(), vec![image::ImageRef {repo: "repo0".to_string(), tag: ver.to_string()}]);
+    }
+    refs.get_mut("1.4-SNAPSHOT").unwrap().push(image::ImageRef {repo: "repo0".to_string(), tag: "latest-snapshot".to_string()});
+    let mut map = HashMap::new();
+    map.insert("repo0".to_string(), entries);
+    let images = image::Images { entries: map, refs, ..Default::default() };
+
+    let tags = PromoteTags {
+        canonical: Some("latest".to_string()), snapshot: Some("latest-snapshot".to_string()), branch: Some("{branch}-latest".to_string())
+    };
+    let removed: HashSet<image::ImageRef> = vec![image::ImageRef {repo: "repo0".to_string(), tag: "1.3".to_string()}].into_iter().collect();
+    let p: Vec<String> = promotions(&images, &removed, &tags).iter().map(|p| p.to_string()).collect();
+    assert_eq!(p, vec![
+        "Promote repo0:1.3-BR102-SNAPSHOT to repo0:BR102-latest",
+        "Promote repo0:1.2-BR103 to repo0:BR103-latest",
+        "Promote repo0:1.2 to repo0:latest",
+    ]);
+
+    let tags = PromoteTags { canonical: Some("latest".to_string()), snapshot: None, branch: None };
+    let p: Vec<String> = promotions(&images, &HashSet::new(), &tags).iter().map(|p| p.to_string()).collect();
+    assert_eq!(p, vec!["Promote repo0:1.3 to repo0:latest"]);
+}
+
+#[test]
+fn promotions_marker_test() {
+    let markers = [version::parse_marker("-SNAPSHOT").unwrap(), version::parse_marker("-dev").unwrap()];
+    let parser = version::parser_with_markers(&markers).unwrap();
+    let mut map = HashMap::new();
+    map.insert("repo0".to_string(), image::entries(&parser, &[("id0", "1.2-dev"), ("id1", "1.5-SNAPSHOT"), ("id2", "1.3-dev")]));
+    let images = image::Images { entries: map, ..Default::default() };
+
+    let tags = PromoteTags { canonical: None, snapshot: Some("latest-snapshot".to_string()), branch: None };
+    let p: Vec<String> = promotions(&images, &HashSet::new(), &tags).iter().map(|p| p.to_string()).collect();
+    assert_eq!(p, vec!["Promote repo0:1.5-SNAPSHOT to repo0:latest-snapshot"]);
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+pub struct ImageInfo {
+    pub full_id: String,
+    pub parent: Option<String>, // Full id of the parent image.
+    pub size: u64,
+    pub layers: Vec<String>, // RootFS layer diff ids from the base.
+    pub labels: HashMap<String, String>,
+}
+
+pub struct DiskUsage {
+    pub used: u64,
+    pub available: u64,
+}
+
+#[derive(Debug)]
+pub enum Target {
+    Free(u64), // bytes
+    Usage(u64), // percent
+}
+
+impl Target {
+    // Usage is calculated in the same way as 'Capacity' of df.
+    pub fn is_reached(&self, usage: &DiskUsage) -> bool {
+        match self {
+            Target::Free(bytes) => *bytes <= usage.available,
+            Target::Usage(percent) => usage.used * 100 <= percent * (usage.used + usage.available),
+        }
+    }
+}
+
+// Parse size such as '50G'. Units are powers of 1024.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => (&s[..idx], &s[idx..]),
+        None => (s, "")
+    };
+    let shift = match &*unit.to_uppercase() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        "T" | "TB" | "TIB" => 40,
+        _ => return Result::Err(format!("Invalid size unit '{}'. Specify K, M, G or T.", unit)),
+    };
+
+    match num.parse::<u64>() {
+        Result::Ok(n) => n.checked_mul(1 << shift).ok_or_else(|| format!("Size '{}' is too large.", s)),
+        Result::Err(_) => Result::Err(format!("Invalid size '{}'.", s)),
+    }
+}
+
+// Parse percent such as '70%'.
+pub fn parse_percent(s: &str) -> Result<u64, String> {
+    match s.trim_end_matches('%').parse() {
+        Result::Ok(p) if p <= 100 => Result::Ok(p),
+        _ => Result::Err(format!("Invalid percent '{}'. Specify 0% - 100%.", s)),
+    }
+}
+
+// Estimate bytes freed by removing each of the deleted images. Returns key: image id.
+// Layer sizes are not available. The size of a layer chain is known only when some image ends
+// exactly at it, so layers shared with kept images are subtracted by the size of such images
+// (typically the base image). Unknown shared layers are attributed to the deleted image.
+pub fn reclaimable(infos: &HashMap<String, ImageInfo>, deleted: &HashSet<String>) -> HashMap<String, u64> {
+    // key: layer chain
+    let mut chain_size: HashMap<&[String], u64> = HashMap::new();
+    let mut kept_chains: HashSet<&[String]> = HashSet::new();
+
+    for (id, info) in infos {
+        chain_size.insert(&info.layers, info.size);
+        if !deleted.contains(id) {
+            for i in 1..=info.layers.len() {
+                kept_chains.insert(&info.layers[..i]);
+            }
+        }
+    }
+
+    let mut ids: Vec<&String> = deleted.iter().filter(|id| infos.contains_key(*id)).collect();
+    ids.sort();
+
+    let mut counted: HashSet<&[String]> = HashSet::new();
+    let mut freed: HashMap<String, u64> = HashMap::new();
+    for id in ids {
+        let layers = &infos[id].layers;
+        let mut prev_size = 0;
+        let mut bytes = 0;
+
+        for i in 1..=layers.len() {
+            let chain = &layers[..i];
+            if let Some(size) = chain_size.get(chain) {
+                if !kept_chains.contains(chain) && counted.insert(chain) {
+                    bytes += size.saturating_sub(prev_size);
+                }
+                prev_size = *size;
+            }
+        }
+
+        freed.insert(id.clone(), bytes);
+    }
+
+    freed
+}
+
+// Parse the output of 'df -Pk'.
+pub fn parse_df(out: &str) -> Option<DiskUsage> {
+    let line = out.lines().nth(1)?;
+    let cols: Vec<&str> = line.split_whitespace().collect();
+    let used: u64 = cols.get(2)?.parse().ok()?;
+    let available: u64 = cols.get(3)?.parse().ok()?;
+
+    Some(DiskUsage { used: used * 1024, available: available * 1024 })
+}
+
+// Units are powers of 1024 as parse_size.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while 1024.0 <= size && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+fn info(size: u64, layers: &[&str]) -> ImageInfo {
+    ImageInfo { full_id: "".to_string(), parent: None, size, layers: layers.iter().map(|l| l.to_string()).collect(), labels: HashMap::new() }
+}
+
+#[test]
+fn reclaimable_test() {
+    let mut infos = HashMap::new();
+    infos.insert("base".to_string(), info(100, &["a"]));
+    infos.insert("app1".to_string(), info(130, &["a", "b"]));
+    infos.insert("app2".to_string(), info(150, &["a", "b", "c"]));
+    infos.insert("app3".to_string(), info(170, &["a", "d"]));
+
+    let deleted: HashSet<String> = vec!["app1".to_string(), "app2".to_string()].into_iter().collect();
+    let freed = reclaimable(&infos, &deleted);
+    assert_eq!(freed["app1"], 30);
+    assert_eq!(freed["app2"], 20);
+
+    // Deleting the base frees nothing since kept app3 uses it.
+    let deleted: HashSet<String> = vec!["base".to_string()].into_iter().collect();
+    let freed = reclaimable(&infos, &deleted);
+    assert_eq!(freed["base"], 0);
+
+    // Shared layer 'b' is kept by app2.
+    let deleted: HashSet<String> = vec!["app1".to_string(), "app3".to_string()].into_iter().collect();
+    let freed = reclaimable(&infos, &deleted);
+    assert_eq!(freed["app1"], 0);
+    assert_eq!(freed["app3"], 70);
+}
+
+#[test]
+fn parse_df_test() {
+    let out = "Filesystem     1024-blocks      Used Available Capacity Mounted on\n\
+               /dev/sda1        102400000  61440000  40960000      60% /\n";
+    let usage = parse_df(out).unwrap();
+    assert_eq!(usage.used, 61440000 * 1024);
+    assert_eq!(usage.available, 40960000 * 1024);
+    assert!(parse_df("").is_none());
+}
+
+#[test]
+fn human_size_test() {
+    assert_eq!(human_size(1023), "1023 B");
+    assert_eq!(human_size(1536), "1.5 KiB");
+    assert_eq!(human_size(38 << 30), "38.0 GiB");
+}
+
+#[test]
+fn parse_size_test() {
+    assert_eq!(parse_size("50G"), Result::Ok(50 * 1024 * 1024 * 1024));
+    assert_eq!(parse_size("512mb"), Result::Ok(512 * 1024 * 1024));
+    assert_eq!(parse_size("1000"), Result::Ok(1000));
+    assert_eq!(parse_size("2GiB"), Result::Ok(2 << 30));
+    assert!(parse_size("50X").is_err());
+    assert!(parse_size("G").is_err());
+    assert!(parse_size("99999999999T").is_err());
+}
+
+#[test]
+fn parse_percent_test() {
+    assert_eq!(parse_percent("70%"), Result::Ok(70));
+    assert_eq!(parse_percent("70"), Result::Ok(70));
+    assert!(parse_percent("101%").is_err());
+    assert!(parse_percent("abc").is_err());
+}
+
+#[test]
+fn target_test() {
+    let usage = DiskUsage { used: 70, available: 30 };
+    assert!(Target::Free(30).is_reached(&usage));
+    assert!(!Target::Free(31).is_reached(&usage));
+    assert!(Target::Usage(70).is_reached(&usage));
+    assert!(!Target::Usage(69).is_reached(&usage));
+}
@@ -5,66 +5,278 @@ use std::hash::{Hash, Hasher};
 use std::fmt;
 
 pub struct Version {
-    pub major: i32,
-    pub minor: i32,  // If minor version does not exist, minor = -1
-    pub patch: i32,  // If patch version does not exist, patch = -1
+    pub numbers: Vec<u64>, // major, minor, patch, ...
     pub is_snapshot: bool,
+    pub marker: Option<String>, // Snapshot marker that matched. None if not a snapshot.
     pub branch: Option<String>,
+    pub stamp: Option<SnapshotStamp>,
+    // Flavour such as 'alpine'. Variants of the same version are equal, so they are kept or deleted together.
+    pub variant: Option<String>,
+    // Component of a monorepo repository such as 'api' of 'api-1.2.3'.
+    pub component: Option<String>,
+    // Build after the release such as '1.2.3-14-gabc1234' made by 'git describe'.
+    pub describe: Option<Describe>,
     pub raw: String,
 }
 
+// Ordered by the commit distance from the release.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Describe {
+    pub distance: u64,
+    pub is_dirty: bool,
+    pub commit: String, // Abbreviated commit hash without 'g'.
+}
+
+impl Version {
+    // (major, minor). Minor is 0 if the version has only one component.
+    pub fn release_line(&self) -> (u64, u64) {
+        (self.numbers[0], *self.numbers.get(1).unwrap_or(&0))
+    }
+}
+
+// Unique snapshot identifier. Maven suffix such as '20241018.123456-3' or
+// the counter of '-SNAPSHOT.3' (timestamp is empty in this case).
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SnapshotStamp {
+    pub timestamp: String, // yyyyMMdd.HHmmss
+    pub build: u64,
+}
+
+pub const DEFAULT_SNAPSHOT_MARKER: &str = "-SNAPSHOT";
+
+// Suffix marker such as '-dev' or prefix marker such as 'dev-'. Matched case sensitively.
+#[derive(PartialEq, Debug)]
+pub struct SnapshotMarker {
+    pub text: String,
+    pub keep_count: Option<usize>,
+}
+
+impl SnapshotMarker {
+    pub fn is_prefix(&self) -> bool {
+        self.text.ends_with('-')
+    }
+}
+
+// Parse 'marker' or 'marker:keep_count'.
+pub fn parse_marker(s: &str) -> Result<SnapshotMarker, String> {
+    let (text, keep_count) = match s.rfind(':') {
+        Some(idx) => match s[idx + 1..].parse() {
+            Result::Ok(count) => (&s[..idx], Some(count)),
+            Result::Err(_) => return Result::Err(format!("Invalid keep count in snapshot marker '{}'.", s)),
+        },
+        None => (s, None)
+    };
+
+    if text.len() < 2 || !(text.starts_with('-') || text.ends_with('-')) {
+        return Result::Err(format!("Invalid snapshot marker '{}'. It should start with '-' (suffix) or end with '-' (prefix).", text));
+    }
+
+    let marker = SnapshotMarker { text: text.to_string(), keep_count };
+    marker_regex(&marker)?;
+    Result::Ok(marker)
+}
+
+fn marker_regex(marker: &SnapshotMarker) -> Result<regex::Regex, String> {
+    let escaped = regex::escape(&marker.text);
+    let re = if marker.is_prefix() {
+        format!(r"^{}(.*)$", escaped)
+    } else {
+        format!(r"^(.*?){}(?:\.(\d+))?$", escaped)
+    };
+    regex::Regex::new(&re).map_err(|err| format!("Invalid snapshot marker '{}'. {}", marker.text, err))
+}
+
 pub struct VersionParser {
-    re: regex::Regex
+    re: regex::Regex,
+    unique_snapshot_re: regex::Regex,
+    describe_re: regex::Regex,
+    suffix_markers: Vec<(String, regex::Regex)>, // (marker, regex)
+    prefix_markers: Vec<(String, regex::Regex)>, // (marker, regex)
+    variants: Vec<String>, // Lower case.
+    is_component_enabled: bool,
 }
 
+#[cfg(test)]
 pub fn parser() -> VersionParser {
-    VersionParser {
-        re: regex::Regex::new(r"^[vV]?(\d{1,5})(\.\d{1,5})?(\.\d{1,5})?(-.*)?$").unwrap()
+    parser_with_markers(&[SnapshotMarker { text: DEFAULT_SNAPSHOT_MARKER.to_string(), keep_count: None }]).unwrap()
+}
+
+pub fn parser_with_markers(markers: &[SnapshotMarker]) -> Result<VersionParser, String> {
+    let mut suffix_markers = Vec::new();
+    let mut prefix_markers = Vec::new();
+
+    for m in markers {
+        let re = marker_regex(m)?;
+        if m.is_prefix() {
+            prefix_markers.push((m.text.clone(), re));
+        } else {
+            suffix_markers.push((m.text.clone(), re));
+        }
     }
+
+    Result::Ok(VersionParser {
+        re: regex::Regex::new(r"^[vV]?(\d+(?:\.\d+)*)(-.*)?$").unwrap(),
+        unique_snapshot_re: regex::Regex::new(r"^(-.*)?-(\d{8}\.\d{6})-(\d+)$").unwrap(),
+        describe_re: regex::Regex::new(r"^-(\d+)-g([0-9a-f]{4,40})(-dirty)?$").unwrap(),
+        suffix_markers,
+        prefix_markers,
+        variants: Vec::new(),
+        is_component_enabled: false,
+    })
 }
 
 impl VersionParser {
+    // Suffixes such as 'alpine' treated as variants instead of branches. Matched case insensitively.
+    pub fn with_variants(mut self, variants: &[String]) -> VersionParser {
+        self.variants = variants.iter().map(|v| v.to_lowercase()).collect();
+        self
+    }
+
+    // Recognize a component prefix such as 'api-' of 'api-1.2.3'.
+    pub fn with_components(mut self, is_enabled: bool) -> VersionParser {
+        self.is_component_enabled = is_enabled;
+        self
+    }
+
+    // Split a variant from the end of the branch such as 'alpine' or 'BR102-alpine'.
+    fn split_variant(&self, branch: Option<String>) -> (Option<String>, Option<String>) {
+        if let Some(b) = &branch {
+            let lower = b.to_lowercase();
+            for v in &self.variants {
+                if lower == *v {
+                    return (None, Some(b.clone()));
+                }
+                if lower.ends_with(&format!("-{}", v)) {
+                    let (br, variant) = b.split_at(b.len() - v.len() - 1);
+                    return (Some(br.to_string()), Some(variant[1..].to_string()));
+                }
+            }
+        }
+        (branch, None)
+    }
+
     pub fn parse(&self, s: &str) -> Option<Version> {
-        self.re.captures(s).map(|caps| {
-            let minor = match caps.get(2) {
-                Some(m) => m.as_str()[1..].parse().unwrap(),
-                None => -1
-            };
-
-            let patch = match caps.get(3) {
-                Some(m) => m.as_str()[1..].parse().unwrap(),
-                None => -1
-            };
-
-            let (branch, is_snapshot) = match caps.get(4) {
-                Some(m) => {
-                    let v: Vec<&str> = m.as_str().split("-SNAPSHOT").collect();
-                    let br = v[0].to_string();
-                    (if br.len() > 1 {Some(br[1..].to_string())} else {None}, v.len() == 2)
-                },
-                None => (None, false)
-            };
+        if let Some(ver) = self.parse_without_component(s) {
+            return Some(ver);
+        }
+        if !self.is_component_enabled {
+            return None;
+        }
 
+        // The shortest component starting with a letter is used, so 'api-dev-1.2' is 'dev-1.2' of 'api'.
+        for (idx, _) in s.match_indices('-') {
+            let component = &s[..idx];
+            if !component.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                return None;
+            }
+            if let Some(mut ver) = self.parse_without_component(&s[idx + 1..]) {
+                ver.component = Some(component.to_string());
+                ver.raw = s.to_string();
+                return Some(ver);
+            }
+        }
+        None
+    }
 
-            Version {
-                major: caps.get(1).unwrap().as_str().parse().unwrap(),
-                minor: minor,
-                patch: patch,
-                is_snapshot: is_snapshot,
-                branch: branch,
-                raw: s.to_string(),
+    fn parse_without_component(&self, s: &str) -> Option<Version> {
+        for (marker, re) in &self.prefix_markers {
+            if let Some(caps) = re.captures(s) {
+                let mut ver = self.parse_without_prefix(caps.get(1).unwrap().as_str())?;
+                ver.is_snapshot = true;
+                ver.marker = Some(marker.clone());
+                ver.raw = s.to_string();
+                return Some(ver);
             }
+        }
+
+        self.parse_without_prefix(s)
+    }
+
+    fn parse_without_prefix(&self, s: &str) -> Option<Version> {
+        let caps = self.re.captures(s)?;
+
+        // Components that do not fit in u64 make the tag unrecognized.
+        let numbers = caps.get(1).unwrap().as_str().split('.')
+            .map(|n| n.parse().ok())
+            .collect::<Option<Vec<u64>>>()?;
+
+        let describe = match caps.get(2).and_then(|m| self.describe_re.captures(m.as_str())) {
+            Some(d) => Some(Describe {
+                distance: d.get(1).unwrap().as_str().parse().ok()?,
+                is_dirty: d.get(3).is_some(),
+                commit: d.get(2).unwrap().as_str().to_string(),
+            }),
+            None => None
+        };
+        let (branch, marker, stamp) = match caps.get(2) {
+            Some(_) if describe.is_some() => (None, None, None),
+            Some(m) => self.parse_suffix(m.as_str())?,
+            None => (None, None, None)
+        };
+        let (branch, variant) = self.split_variant(branch);
+
+        Some(Version {
+            numbers,
+            is_snapshot: marker.is_some(),
+            marker,
+            branch,
+            stamp,
+            variant,
+            component: None,
+            describe,
+            raw: s.to_string(),
         })
     }
+
+    // Returns (branch, marker, stamp).
+    fn parse_suffix(&self, suffix: &str) -> Option<(Option<String>, Option<String>, Option<SnapshotStamp>)> {
+        if let Some(unique) = self.unique_snapshot_re.captures(suffix) {
+            return Some((
+                match unique.get(1) {
+                    Some(br) if br.as_str().len() > 1 => Some(br.as_str()[1..].to_string()),
+                    Some(_) => return None,
+                    None => None
+                },
+                Some(DEFAULT_SNAPSHOT_MARKER.to_string()),
+                Some(SnapshotStamp {
+                    timestamp: unique.get(2).unwrap().as_str().to_string(),
+                    build: unique.get(3).unwrap().as_str().parse().ok()?,
+                })
+            ));
+        }
+
+        for (marker, re) in &self.suffix_markers {
+            if let Some(caps) = re.captures(suffix) {
+                let br = caps.get(1).unwrap().as_str();
+                let stamp = match caps.get(2) {
+                    Some(n) => Some(SnapshotStamp { timestamp: "".to_string(), build: n.as_str().parse().ok()? }),
+                    None => None
+                };
+                return Some((
+                    if br.len() > 1 {Some(br[1..].to_string())} else {None},
+                    Some(marker.clone()),
+                    stamp
+                ));
+            }
+        }
+
+        if suffix.len() > 1 {
+            Some((Some(suffix[1..].to_string()), None, None))
+        } else {
+            None
+        }
+    }
 }
 
 impl PartialEq for Version {
     fn eq(&self, other: &Self) -> bool {
-        self.major == other.major &&
-            self.minor == other.minor &&
-            self.patch == other.patch &&
+        self.component == other.component &&
+            self.numbers == other.numbers &&
             self.is_snapshot == other.is_snapshot &&
-            self.branch == other.branch
+            self.marker == other.marker &&
+            self.branch == other.branch &&
+            self.stamp == other.stamp &&
+            self.describe == other.describe
     }
 }
 
@@ -72,11 +284,13 @@ impl Eq for Version {}
 
 impl Hash for Version {
     fn hash<H:Hasher>(&self, state: &mut H) {
-        self.major.hash(state);
-        self.minor.hash(state);
-        self.patch.hash(state);
+        self.component.hash(state);
+        self.numbers.hash(state);
         self.is_snapshot.hash(state);
+        self.marker.hash(state);
         self.branch.hash(state);
+        self.stamp.hash(state);
+        self.describe.hash(state);
     }
 }
 
@@ -88,34 +302,46 @@ impl fmt::Display for Version {
 
 impl fmt::Debug for Version {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Version [major: {}, minor: {}, patch: {}, branch: {:?}, is_snapshot: {}]",
-               self.major, self.minor, self.patch, self.branch, self.is_snapshot)
+        write!(f, "Version [numbers: {:?}, branch: {:?}, is_snapshot: {}, marker: {:?}, stamp: {:?}, variant: {:?}, component: {:?}, describe: {:?}]",
+               self.numbers, self.branch, self.is_snapshot, self.marker, self.stamp, self.variant, self.component, self.describe)
     }
 }
 
+// Numbers are compared component-wise. A missing component sorts before any present one: 1 < 1.0 < 1.0.0.
+// Unique snapshots of the same numbers are ordered by timestamp and build number.
 impl Ord for Version {
     fn cmp(&self, other: &Self) -> Ordering {
+        let co = self.component.cmp(&other.component);
+        if co != Ordering::Equal {
+            return co;
+        }
+
         let is = self.is_snapshot.cmp(&other.is_snapshot);
         if is != Ordering:: Equal {
             return is;
         }
 
+        let mk = self.marker.cmp(&other.marker);
+        if mk != Ordering::Equal {
+            return mk;
+        }
+
         let br = self.branch.cmp(&other.branch);
         if br != Ordering::Equal {
             return br;
         }
 
-        let ma = self.major.cmp(&other.major);
-        if ma != Ordering::Equal {
-            return ma;
+        let nu = self.numbers.cmp(&other.numbers);
+        if nu != Ordering::Equal {
+            return nu;
         }
 
-        let mi = self.minor.cmp(&other.minor);
-        if mi != Ordering::Equal {
-            return mi;
+        let de = self.describe.cmp(&other.describe);
+        if de != Ordering::Equal {
+            return de;
         }
 
-        self.patch.cmp(&other.patch)
+        self.stamp.cmp(&other.stamp)
     }
 }
 
@@ -126,87 +352,80 @@ impl PartialOrd for Version {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_major_minor_patch() {
     let v = parser().parse("1.2.3").unwrap();
-    assert_eq!(v.major, 1);
-    assert_eq!(v.minor, 2);
-    assert_eq!(v.patch, 3);
+    assert_eq!(v.numbers, vec![1, 2, 3]);
     assert_eq!(v.is_snapshot, false);
     assert_eq!(v.branch, None);
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_major_minor_patch_snapshot() {
     let v = parser().parse("1.2.3-SNAPSHOT").unwrap();
-    assert_eq!(v.major, 1);
-    assert_eq!(v.minor, 2);
-    assert_eq!(v.patch, 3);
+    assert_eq!(v.numbers, vec![1, 2, 3]);
     assert_eq!(v.is_snapshot, true);
     assert_eq!(v.branch, None);
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_major_minor() {
     let v = parser().parse("1.2").unwrap();
-    assert_eq!(v.major, 1);
-    assert_eq!(v.minor, 2);
-    assert_eq!(v.patch, -1);
+    assert_eq!(v.numbers, vec![1, 2]);
     assert_eq!(v.is_snapshot, false);
     assert_eq!(v.branch, None);
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_major() {
     let v = parser().parse("1").unwrap();
-    assert_eq!(v.major, 1);
-    assert_eq!(v.minor, -1);
-    assert_eq!(v.patch, -1);
+    assert_eq!(v.numbers, vec![1]);
     assert_eq!(v.is_snapshot, false);
     assert_eq!(v.branch, None);
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_branch() {
     let v = parser().parse("1.2-BR102").unwrap();
-    assert_eq!(v.major, 1);
-    assert_eq!(v.minor, 2);
-    assert_eq!(v.patch, -1);
+    assert_eq!(v.numbers, vec![1, 2]);
     assert_eq!(v.is_snapshot, false);
     assert_eq!(v.branch, Some("BR102".to_string()));
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_branch_snapshot() {
     let v = parser().parse("1.2-BR102-SNAPSHOT").unwrap();
-    assert_eq!(v.major, 1);
-    assert_eq!(v.minor, 2);
-    assert_eq!(v.patch, -1);
+    assert_eq!(v.numbers, vec![1, 2]);
     assert_eq!(v.is_snapshot, true);
     assert_eq!(v.branch, Some("BR102".to_string()));
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn latest() {
     assert_eq!(parser().parse("latest").is_none(), true);
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn v() {
     let v = parser().parse("v0.31.2").unwrap();
-    assert_eq!(v.major, 0);
-    assert_eq!(v.minor, 31);
-    assert_eq!(v.patch, 2);
+    assert_eq!(v.numbers, vec![0, 31, 2]);
     assert_eq!(v.is_snapshot, false);
     assert_eq!(v.branch, None);
     assert_eq!(format!("{}", v), "v0.31.2");
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn sdk8() {
     let v = parser().parse("8-sdk").unwrap();
-    assert_eq!(v.major, 8);
-    assert_eq!(v.minor, -1);
-    assert_eq!(v.patch, -1);
+    assert_eq!(v.numbers, vec![8]);
     assert_eq!(v.is_snapshot, false);
     assert_eq!(v.branch, Some("sdk".to_string()));
 }
@@ -230,6 +449,7 @@ fn major_minor_patch_cmp() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn major_minor_patch_cmp_snapshot() {
     let parser = parser();
     assert_eq!(parser.parse("1.2.3-SNAPSHOT").unwrap().cmp(&parser.parse("1.2.3-SNAPSHOT").unwrap()), Ordering::Equal);
@@ -249,6 +469,7 @@ fn major_minor_patch_cmp_snapshot() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn branch() {
     let parser = parser();
     assert_eq!(parser.parse("1.2.3-BR123").unwrap().cmp(&parser.parse("1.2.3-BR123").unwrap()), Ordering::Equal);
@@ -268,6 +489,7 @@ fn branch() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn branch_snapshot() {
     let parser = parser();
     assert_eq!(parser.parse("1.2.3-BR123-SNAPSHOT").unwrap().cmp(&parser.parse("1.2.3-BR123-SNAPSHOT").unwrap()), Ordering::Equal);
@@ -285,3 +507,230 @@ fn branch_snapshot() {
 
     assert_eq!(parser.parse("2-BR123").unwrap().cmp(&parser.parse("2-BR123-SNAPSHOT").unwrap()) != Ordering::Equal, true);
 }
+
+#[test]
+fn build_number() {
+    let v = parser().parse("104233").unwrap();
+    assert_eq!(v.numbers, vec![104233]);
+    assert!(!v.is_snapshot);
+    assert_eq!(v.branch, None);
+
+    let v = parser().parse("20201018123456.1").unwrap();
+    assert_eq!(v.numbers, vec![20201018123456, 1]);
+}
+
+#[test]
+fn too_large_number() {
+    assert!(parser().parse("123456789012345678901234567890").is_none());
+    assert!(parser().parse("1.123456789012345678901234567890").is_none());
+}
+
+#[test]
+fn missing_component_cmp() {
+    let parser = parser();
+    assert_eq!(parser.parse("1").unwrap().cmp(&parser.parse("1.0").unwrap()), Ordering::Less);
+    assert_eq!(parser.parse("1.0").unwrap().cmp(&parser.parse("1.0.0").unwrap()), Ordering::Less);
+    assert_eq!(parser.parse("1.0.0").unwrap().cmp(&parser.parse("1.1").unwrap()), Ordering::Less);
+    assert_eq!(parser.parse("104233").unwrap().cmp(&parser.parse("99999").unwrap()), Ordering::Greater);
+}
+
+#[test]
+fn four_components() {
+    let v = parser().parse("10.0.19041.1").unwrap();
+    assert_eq!(v.numbers, vec![10, 0, 19041, 1]);
+    assert!(!v.is_snapshot);
+    assert_eq!(v.branch, None);
+
+    let v = parser().parse("1.2.3.4-foo-SNAPSHOT").unwrap();
+    assert_eq!(v.numbers, vec![1, 2, 3, 4]);
+    assert!(v.is_snapshot);
+    assert_eq!(v.branch, Some("foo".to_string()));
+}
+
+#[test]
+fn four_components_cmp() {
+    let parser = parser();
+    assert_eq!(parser.parse("1.2.3.4").unwrap().cmp(&parser.parse("1.2.3.4").unwrap()), Ordering::Equal);
+    assert_eq!(parser.parse("1.2.3.4").unwrap().cmp(&parser.parse("1.2.3.10").unwrap()), Ordering::Less);
+    assert_eq!(parser.parse("1.2.3").unwrap().cmp(&parser.parse("1.2.3.0").unwrap()), Ordering::Less);
+    assert_eq!(parser.parse("1.2.4").unwrap().cmp(&parser.parse("1.2.3.9").unwrap()), Ordering::Greater);
+    assert_eq!(parser.parse("10.0.19041.1").unwrap().cmp(&parser.parse("10.0.17763.1").unwrap()), Ordering::Greater);
+}
+
+#[test]
+fn unique_snapshot() {
+    let v = parser().parse("1.2-20241018.123456-3").unwrap();
+    assert_eq!(v.numbers, vec![1, 2]);
+    assert!(v.is_snapshot);
+    assert_eq!(v.branch, None);
+    assert_eq!(v.stamp, Some(SnapshotStamp { timestamp: "20241018.123456".to_string(), build: 3 }));
+
+    let v = parser().parse("1.2-BR102-20241018.123456-3").unwrap();
+    assert_eq!(v.numbers, vec![1, 2]);
+    assert!(v.is_snapshot);
+    assert_eq!(v.branch, Some("BR102".to_string()));
+    assert_eq!(v.stamp, Some(SnapshotStamp { timestamp: "20241018.123456".to_string(), build: 3 }));
+}
+
+#[test]
+fn unique_snapshot_cmp() {
+    let parser = parser();
+    assert_eq!(parser.parse("1.2-20241018.123456-3").unwrap().cmp(&parser.parse("1.2-20241018.123456-3").unwrap()), Ordering::Equal);
+    assert_eq!(parser.parse("1.2-20241018.123456-3").unwrap().cmp(&parser.parse("1.2-20241019.000000-1").unwrap()), Ordering::Less);
+    assert_eq!(parser.parse("1.2-20241018.123456-3").unwrap().cmp(&parser.parse("1.2-20241018.123456-10").unwrap()), Ordering::Less);
+    assert_eq!(parser.parse("1.3-20241001.000000-1").unwrap().cmp(&parser.parse("1.2-20241018.123456-3").unwrap()), Ordering::Greater);
+    assert_eq!(parser.parse("1.2-SNAPSHOT").unwrap().cmp(&parser.parse("1.2-20241018.123456-3").unwrap()), Ordering::Less);
+}
+
+#[cfg(test)]
+fn marker_parser() -> VersionParser {
+    parser_with_markers(&[
+        parse_marker("-SNAPSHOT").unwrap(),
+        parse_marker("-dev").unwrap(),
+        parse_marker("-nightly:3").unwrap(),
+        parse_marker("dev-").unwrap(),
+    ]).unwrap()
+}
+
+#[test]
+fn parse_marker_test() {
+    assert_eq!(parse_marker("-dev"), Result::Ok(SnapshotMarker { text: "-dev".to_string(), keep_count: None }));
+    assert_eq!(parse_marker("-nightly:3"), Result::Ok(SnapshotMarker { text: "-nightly".to_string(), keep_count: Some(3) }));
+    assert!(parse_marker("dev-").unwrap().is_prefix());
+    assert!(!parse_marker("-dev").unwrap().is_prefix());
+    assert!(parse_marker("dev").is_err());
+    assert!(parse_marker("-").is_err());
+    assert!(parse_marker("-dev:x").is_err());
+}
+
+#[test]
+fn empty_branch() {
+    assert!(parser().parse("1.2-").is_none());
+    assert!(parser().parse("1.2--20241018.123456-3").is_none());
+}
+
+#[test]
+fn snapshot_marker() {
+    let parser = marker_parser();
+
+    let v = parser.parse("1.2-snapshot").unwrap();
+    assert!(!v.is_snapshot);
+    assert_eq!(v.marker, None);
+    assert_eq!(v.branch, Some("snapshot".to_string()));
+
+    let v = parser.parse("1.2-BR102-dev").unwrap();
+    assert_eq!(v.numbers, vec![1, 2]);
+    assert!(v.is_snapshot);
+    assert_eq!(v.marker, Some("-dev".to_string()));
+    assert_eq!(v.branch, Some("BR102".to_string()));
+
+    let v = parser.parse("1.2-SNAPSHOT.12").unwrap();
+    assert!(v.is_snapshot);
+    assert_eq!(v.marker, Some("-SNAPSHOT".to_string()));
+    assert_eq!(v.branch, None);
+    assert_eq!(v.stamp, Some(SnapshotStamp { timestamp: "".to_string(), build: 12 }));
+
+    let v = parser.parse("dev-1.2").unwrap();
+    assert_eq!(v.numbers, vec![1, 2]);
+    assert!(v.is_snapshot);
+    assert_eq!(v.marker, Some("dev-".to_string()));
+    assert_eq!(v.branch, None);
+    assert_eq!(format!("{}", v), "dev-1.2");
+
+    let v = parser.parse("1.2-devel").unwrap();
+    assert!(!v.is_snapshot);
+    assert_eq!(v.branch, Some("devel".to_string()));
+
+    assert!(super::version::parser().parse("1.2-dev").unwrap().marker.is_none());
+}
+
+#[test]
+fn snapshot_marker_cmp() {
+    let parser = marker_parser();
+    assert_eq!(parser.parse("1.2-SNAPSHOT.2").unwrap().cmp(&parser.parse("1.2-SNAPSHOT.10").unwrap()), Ordering::Less);
+    assert_eq!(parser.parse("1.2-SNAPSHOT").unwrap().cmp(&parser.parse("1.2-SNAPSHOT.1").unwrap()), Ordering::Less);
+    assert!(parser.parse("1.2-dev").unwrap().cmp(&parser.parse("1.2-SNAPSHOT").unwrap()) != Ordering::Equal);
+    assert!(parser.parse("dev-1.2").unwrap().cmp(&parser.parse("1.2-dev").unwrap()) != Ordering::Equal);
+}
+
+#[test]
+fn release_line() {
+    let parser = parser();
+    assert_eq!(parser.parse("2.4.3").unwrap().release_line(), (2, 4));
+    assert_eq!(parser.parse("v2.4").unwrap().release_line(), (2, 4));
+    assert_eq!(parser.parse("104233").unwrap().release_line(), (104233, 0));
+}
+
+#[test]
+fn variant() {
+    let parser = parser().with_variants(&["alpine".to_string(), "sdk".to_string()]);
+    let v = parser.parse("1.2-alpine").unwrap();
+    assert_eq!(v.branch, None);
+    assert_eq!(v.variant, Some("alpine".to_string()));
+    assert_eq!(v, parser.parse("1.2").unwrap());
+
+    let v = parser.parse("8-SDK").unwrap();
+    assert_eq!(v.numbers, vec![8]);
+    assert_eq!(v.variant, Some("SDK".to_string()));
+
+    let v = parser.parse("1.2-BR102-alpine-SNAPSHOT").unwrap();
+    assert_eq!(v.branch, Some("BR102".to_string()));
+    assert_eq!(v.variant, Some("alpine".to_string()));
+    assert!(v.is_snapshot);
+    assert_eq!(v, parser.parse("1.2-BR102-SNAPSHOT").unwrap());
+
+    let v = parser.parse("1.2-jdk").unwrap();
+    assert_eq!(v.branch, Some("jdk".to_string()));
+    assert_eq!(v.variant, None);
+}
+
+#[test]
+fn component() {
+    assert!(parser().parse("api-1.2.3").is_none());
+
+    let parser = parser_with_markers(&[
+        SnapshotMarker { text: DEFAULT_SNAPSHOT_MARKER.to_string(), keep_count: None },
+        SnapshotMarker { text: "dev-".to_string(), keep_count: None },
+    ]).unwrap().with_components(true);
+    let v = parser.parse("api-1.2.3").unwrap();
+    assert_eq!(v.component, Some("api".to_string()));
+    assert_eq!(v.numbers, vec![1, 2, 3]);
+    assert_eq!(v.raw, "api-1.2.3");
+
+    let v = parser.parse("web-v2.0-SNAPSHOT").unwrap();
+    assert_eq!(v.component, Some("web".to_string()));
+    assert!(v.is_snapshot);
+
+    let v = parser.parse("my-worker-1.4.0").unwrap();
+    assert_eq!(v.component, Some("my-worker".to_string()));
+
+    let v = parser.parse("api-dev-1.2").unwrap();
+    assert_eq!(v.component, Some("api".to_string()));
+    assert_eq!(v.marker, Some("dev-".to_string()));
+
+    assert_eq!(parser.parse("dev-1.2").unwrap().component, None);
+    assert_eq!(parser.parse("1.2-BR102").unwrap().component, None);
+    assert!(parser.parse("api-latest").is_none());
+    assert!(parser.parse("api-1.2") != parser.parse("web-1.2"));
+    assert!(parser.parse("api-1.3") < parser.parse("web-1.2"));
+}
+
+#[test]
+fn describe() {
+    let parser = parser();
+    let v = parser.parse("1.2.3-14-gabc1234").unwrap();
+    assert_eq!(v.numbers, vec![1, 2, 3]);
+    assert_eq!(v.branch, None);
+    assert!(!v.is_snapshot);
+    assert_eq!(v.describe, Some(Describe { distance: 14, is_dirty: false, commit: "abc1234".to_string() }));
+
+    let v = parser.parse("v1.2.3-14-gabc1234-dirty").unwrap();
+    assert!(v.describe.unwrap().is_dirty);
+
+    assert!(parser.parse("1.2.3").unwrap() < parser.parse("1.2.3-2-gdef5678").unwrap());
+    assert!(parser.parse("1.2.3-2-gdef5678").unwrap() < parser.parse("1.2.3-14-gabc1234").unwrap());
+    assert!(parser.parse("1.2.3-14-gabc1234").unwrap() < parser.parse("1.2.4-1-g0123abc").unwrap());
+
+    // Not a commit hash.
+    assert_eq!(parser.parse("1.2.3-14-gxyz").unwrap().branch, Some("14-gxyz".to_string()));
+}